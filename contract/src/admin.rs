@@ -0,0 +1,182 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// First step of an ownership transfer, the new owner has to accept it
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        Event::OwnerProposed {
+            owner: self.owner.clone(),
+            proposed_owner: new_owner.clone(),
+        }
+        .emit();
+        self.proposed_owner = Some(new_owner);
+    }
+
+    pub fn accept_ownership(&mut self) {
+        let caller = env::predecessor_account_id();
+        require(
+            self.proposed_owner.as_ref() == Some(&caller),
+            ContractError::NotProposedOwner,
+        );
+        Event::OwnershipTransferred {
+            previous_owner: self.owner.clone(),
+            owner: caller.clone(),
+        }
+        .emit();
+        self.owner = caller;
+        self.proposed_owner = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        self.paused = true;
+        Event::ContractPaused {
+            owner: self.owner.clone(),
+        }
+        .emit();
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        self.paused = false;
+        Event::ContractUnpaused {
+            owner: self.owner.clone(),
+        }
+        .emit();
+    }
+
+    /// Hides a crowdfund from listings and stops it from taking donations and votes
+    pub fn set_crowdfund_delisted(&mut self, id: u64, delisted: bool) {
+        self.assert_owner();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.delisted = delisted;
+        self.crowdfunds.replace(id, &crowdfund);
+        Event::CrowdfundDelisted {
+            crowdfund_id: id,
+            delisted,
+        }
+        .emit();
+    }
+
+    /// Blocks every change to a crowdfund, including withdrawals and refunds
+    pub fn set_crowdfund_frozen(&mut self, id: u64, frozen: bool) {
+        self.assert_owner();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.frozen = frozen;
+        self.crowdfunds.replace(id, &crowdfund);
+        Event::CrowdfundFrozen {
+            crowdfund_id: id,
+            frozen,
+        }
+        .emit();
+    }
+
+    pub fn set_config(&mut self, config: PlatformConfigView) {
+        self.assert_owner();
+        require(
+            config.fee_bps as u128 <= BASIS_POINTS,
+            ContractError::InvalidFee,
+        );
+        require(
+            config.max_duration_days > 0 && config.max_duration_days <= MAX_DURATION_DAYS,
+            ContractError::InvalidDuration,
+        );
+        Event::ConfigUpdated {
+            min_donation: config.min_donation,
+            max_duration_days: config.max_duration_days,
+            fee_bps: config.fee_bps,
+        }
+        .emit();
+        self.config = config.into();
+    }
+
+    /// Whitelists a NEP-141 token that crowdfunds may take donations in
+    pub fn add_accepted_token(&mut self, token_id: AccountId) {
+        self.assert_owner();
+        self.accepted_tokens.insert(&token_id);
+        Event::AcceptedTokenUpdated {
+            token_id,
+            accepted: true,
+        }
+        .emit();
+    }
+
+    /// Stops new donations in a token, escrowed amounts can still be withdrawn or refunded
+    pub fn remove_accepted_token(&mut self, token_id: AccountId) {
+        self.assert_owner();
+        self.accepted_tokens.remove(&token_id);
+        Event::AcceptedTokenUpdated {
+            token_id,
+            accepted: false,
+        }
+        .emit();
+    }
+
+    /// Sends accrued platform fees to the owner
+    pub fn withdraw_treasury(&mut self, amount: U128) -> Promise {
+        self.assert_owner();
+        let amount = amount.0;
+        require(
+            amount > 0 && amount <= self.treasury,
+            ContractError::InsufficientTreasury,
+        );
+        self.treasury -= amount;
+        Promise::new(self.owner.clone())
+            .transfer(amount)
+            .then(ext_self::on_withdraw_treasury(
+                U128(amount),
+                &env::current_account_id(),
+                0,
+                XCC_GAS,
+            ))
+    }
+
+    /// Callback for `withdraw_treasury`, restores the treasury if the transfer failed
+    pub fn on_withdraw_treasury(&mut self, amount: U128) {
+        assert_self();
+        if is_promise_success() {
+            Event::TreasuryWithdrawn {
+                owner: self.owner.clone(),
+                amount,
+            }
+            .emit();
+            return;
+        }
+        self.treasury += amount.0;
+        Event::TreasuryWithdrawalFailed {
+            owner: self.owner.clone(),
+            amount,
+        }
+        .emit();
+    }
+
+    pub fn get_treasury(&self) -> U128 {
+        U128(self.treasury)
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+
+    pub fn get_config(&self) -> PlatformConfigView {
+        self.config.clone().into()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        require(
+            env::predecessor_account_id() == self.owner,
+            ContractError::NotOwner,
+        );
+    }
+
+    pub(crate) fn assert_not_paused(&self) {
+        require(!self.paused, ContractError::Paused);
+    }
+}
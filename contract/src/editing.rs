@@ -0,0 +1,61 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Lets the creator change the title, description, media or target (in yocto) of an
+    /// active crowdfund. The replaced version is kept for donors
+    pub fn edit_crowdfund(
+        &mut self,
+        id: u64,
+        title: Option<String>,
+        description: Option<String>,
+        donation_target: Option<U128>,
+        media: Option<String>,
+    ) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_creator();
+        crowdfund.assert_not_frozen();
+        crowdfund.assert_status(CrowdfundStatus::Active, ContractError::CampaignClosed);
+        let initial_storage = env::storage_usage();
+        let revision = crowdfund.edit(
+            title,
+            description,
+            donation_target.map(|donation_target| donation_target.0),
+            media,
+        );
+
+        let mut revisions = self
+            .revisions_by_crowdfund
+            .get(&id)
+            .unwrap_or_else(|| Vector::new(StorageKey::CrowdfundRevisions { crowdfund_id: id }));
+        revisions.push(&revision);
+        self.revisions_by_crowdfund.insert(&id, &revisions);
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&crowdfund.creator, initial_storage);
+        Event::CrowdfundEdited {
+            crowdfund_id: id,
+            version: crowdfund.version,
+            title: crowdfund.title,
+            donation_target: U128(crowdfund.donation_target),
+        }
+        .emit();
+    }
+
+    /// Earlier versions of a crowdfund, oldest first. The current one is the crowdfund itself
+    pub fn get_crowdfund_revisions(
+        &self,
+        id: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<CrowdfundRevisionView> {
+        let revisions = match self.revisions_by_crowdfund.get(&id) {
+            Some(revisions) => revisions,
+            None => return vec![],
+        };
+        let to_index = std::cmp::min(from_index.saturating_add(limit), revisions.len());
+        (from_index..to_index)
+            .map(|index| revisions.get(index).unwrap().into())
+            .collect()
+    }
+}
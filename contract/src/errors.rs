@@ -0,0 +1,246 @@
+use near_sdk::env;
+
+/// Failures surfaced to callers. Panic messages have the form `<code>: <message>`,
+/// the code is stable so frontends can map it to UI states.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContractError {
+    AlreadyInitialized,
+    CrowdfundNotFound,
+    CampaignClosed,
+    NotCreator,
+    DepositTooSmall,
+    TargetNotReached,
+    NothingToWithdraw,
+    NotRefundable,
+    NothingToRefund,
+    AlreadyVoted,
+    NotVoted,
+    InvalidDuration,
+    NotOwner,
+    NotProposedOwner,
+    Paused,
+    CrowdfundFrozen,
+    CrowdfundDelisted,
+    TokenNotAccepted,
+    TokenTargetFixed,
+    InvalidTransferMessage,
+    InvalidFee,
+    InsufficientTreasury,
+    InvalidMilestones,
+    MilestonesPending,
+    MilestoneNotDue,
+    NotBacker,
+    VotingClosed,
+    VotingNotEnded,
+    RewardTierNotFound,
+    RewardTierSoldOut,
+    PledgeBelowTierMinimum,
+    InvalidRewardTier,
+    TooManyRewardTiers,
+    BadgeNotFound,
+    NotBadgeOwner,
+    InvalidBadgeReceiver,
+    RoundNotFound,
+    RoundClosed,
+    RoundNotEnded,
+    RoundFull,
+    AlreadyInRound,
+    InvalidMatchCap,
+    NoMatch,
+    MatchPending,
+    NothingToReclaim,
+    StorageNotRegistered,
+    StorageDepositTooSmall,
+    InsufficientStorage,
+    StorageInUse,
+    NothingToEdit,
+    InvalidTarget,
+    NotCancellable,
+    StateNotFound,
+    UnknownStateVersion,
+    MissingCode,
+    NotEnoughGas,
+    InvalidTitle,
+    InvalidDescription,
+    InvalidCancelReason,
+    InvalidMedia,
+    PledgeNotFound,
+    NotPledgeDonor,
+    PledgeClosed,
+    InvalidPledge,
+}
+
+impl ContractError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::AlreadyInitialized => "ERR_ALREADY_INITIALIZED",
+            ContractError::CrowdfundNotFound => "ERR_CROWDFUND_NOT_FOUND",
+            ContractError::CampaignClosed => "ERR_CAMPAIGN_CLOSED",
+            ContractError::NotCreator => "ERR_NOT_CREATOR",
+            ContractError::DepositTooSmall => "ERR_DEPOSIT_TOO_SMALL",
+            ContractError::TargetNotReached => "ERR_TARGET_NOT_REACHED",
+            ContractError::NothingToWithdraw => "ERR_NOTHING_TO_WITHDRAW",
+            ContractError::NotRefundable => "ERR_NOT_REFUNDABLE",
+            ContractError::NothingToRefund => "ERR_NOTHING_TO_REFUND",
+            ContractError::AlreadyVoted => "ERR_ALREADY_VOTED",
+            ContractError::NotVoted => "ERR_NOT_VOTED",
+            ContractError::InvalidDuration => "ERR_INVALID_DURATION",
+            ContractError::NotOwner => "ERR_NOT_OWNER",
+            ContractError::NotProposedOwner => "ERR_NOT_PROPOSED_OWNER",
+            ContractError::Paused => "ERR_PAUSED",
+            ContractError::CrowdfundFrozen => "ERR_CROWDFUND_FROZEN",
+            ContractError::CrowdfundDelisted => "ERR_CROWDFUND_DELISTED",
+            ContractError::TokenNotAccepted => "ERR_TOKEN_NOT_ACCEPTED",
+            ContractError::TokenTargetFixed => "ERR_TOKEN_TARGET_FIXED",
+            ContractError::InvalidTransferMessage => "ERR_INVALID_TRANSFER_MESSAGE",
+            ContractError::InvalidFee => "ERR_INVALID_FEE",
+            ContractError::InsufficientTreasury => "ERR_INSUFFICIENT_TREASURY",
+            ContractError::InvalidMilestones => "ERR_INVALID_MILESTONES",
+            ContractError::MilestonesPending => "ERR_MILESTONES_PENDING",
+            ContractError::MilestoneNotDue => "ERR_MILESTONE_NOT_DUE",
+            ContractError::NotBacker => "ERR_NOT_BACKER",
+            ContractError::VotingClosed => "ERR_VOTING_CLOSED",
+            ContractError::VotingNotEnded => "ERR_VOTING_NOT_ENDED",
+            ContractError::RewardTierNotFound => "ERR_REWARD_TIER_NOT_FOUND",
+            ContractError::RewardTierSoldOut => "ERR_REWARD_TIER_SOLD_OUT",
+            ContractError::PledgeBelowTierMinimum => "ERR_PLEDGE_BELOW_TIER_MINIMUM",
+            ContractError::InvalidRewardTier => "ERR_INVALID_REWARD_TIER",
+            ContractError::TooManyRewardTiers => "ERR_TOO_MANY_REWARD_TIERS",
+            ContractError::BadgeNotFound => "ERR_BADGE_NOT_FOUND",
+            ContractError::NotBadgeOwner => "ERR_NOT_BADGE_OWNER",
+            ContractError::InvalidBadgeReceiver => "ERR_INVALID_BADGE_RECEIVER",
+            ContractError::RoundNotFound => "ERR_ROUND_NOT_FOUND",
+            ContractError::RoundClosed => "ERR_ROUND_CLOSED",
+            ContractError::RoundNotEnded => "ERR_ROUND_NOT_ENDED",
+            ContractError::RoundFull => "ERR_ROUND_FULL",
+            ContractError::AlreadyInRound => "ERR_ALREADY_IN_ROUND",
+            ContractError::InvalidMatchCap => "ERR_INVALID_MATCH_CAP",
+            ContractError::NoMatch => "ERR_NO_MATCH",
+            ContractError::MatchPending => "ERR_MATCH_PENDING",
+            ContractError::NothingToReclaim => "ERR_NOTHING_TO_RECLAIM",
+            ContractError::StorageNotRegistered => "ERR_STORAGE_NOT_REGISTERED",
+            ContractError::StorageDepositTooSmall => "ERR_STORAGE_DEPOSIT_TOO_SMALL",
+            ContractError::InsufficientStorage => "ERR_INSUFFICIENT_STORAGE",
+            ContractError::StorageInUse => "ERR_STORAGE_IN_USE",
+            ContractError::NothingToEdit => "ERR_NOTHING_TO_EDIT",
+            ContractError::InvalidTarget => "ERR_INVALID_TARGET",
+            ContractError::NotCancellable => "ERR_NOT_CANCELLABLE",
+            ContractError::StateNotFound => "ERR_STATE_NOT_FOUND",
+            ContractError::UnknownStateVersion => "ERR_UNKNOWN_STATE_VERSION",
+            ContractError::MissingCode => "ERR_MISSING_CODE",
+            ContractError::NotEnoughGas => "ERR_NOT_ENOUGH_GAS",
+            ContractError::InvalidTitle => "ERR_INVALID_TITLE",
+            ContractError::InvalidDescription => "ERR_INVALID_DESCRIPTION",
+            ContractError::InvalidCancelReason => "ERR_INVALID_CANCEL_REASON",
+            ContractError::InvalidMedia => "ERR_INVALID_MEDIA",
+            ContractError::PledgeNotFound => "ERR_PLEDGE_NOT_FOUND",
+            ContractError::NotPledgeDonor => "ERR_NOT_PLEDGE_DONOR",
+            ContractError::PledgeClosed => "ERR_PLEDGE_CLOSED",
+            ContractError::InvalidPledge => "ERR_INVALID_PLEDGE",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ContractError::AlreadyInitialized => "Contract is already initialized",
+            ContractError::CrowdfundNotFound => "Crowdfund does not exist",
+            ContractError::CampaignClosed => "Campaign is not active",
+            ContractError::NotCreator => "Only the creator of the crowdfund may do this",
+            ContractError::DepositTooSmall => "Attached deposit is below the minimum donation",
+            ContractError::TargetNotReached => "Campaign has not reached its target",
+            ContractError::NothingToWithdraw => "Nothing to withdraw",
+            ContractError::NotRefundable => "Campaign is not refundable",
+            ContractError::NothingToRefund => "Nothing to refund",
+            ContractError::AlreadyVoted => "Already voted for this campaign",
+            ContractError::NotVoted => "Has not voted for this campaign",
+            ContractError::InvalidDuration => "Campaign duration is outside the allowed range",
+            ContractError::NotOwner => "Only the contract owner may do this",
+            ContractError::NotProposedOwner => "Only the proposed owner may accept ownership",
+            ContractError::Paused => "Contract is paused",
+            ContractError::CrowdfundFrozen => "Crowdfund is frozen",
+            ContractError::CrowdfundDelisted => "Crowdfund is delisted",
+            ContractError::TokenNotAccepted => "Token is not accepted for donations",
+            ContractError::TokenTargetFixed => {
+                "Token target cannot change once the token received donations"
+            }
+            ContractError::InvalidTransferMessage => {
+                "Transfer message must be a JSON object naming the crowdfund_id"
+            }
+            ContractError::InvalidFee => "Platform fee cannot exceed 10000 basis points",
+            ContractError::InsufficientTreasury => "Treasury balance is too low",
+            ContractError::InvalidMilestones => {
+                "Milestones must share 10000 basis points and fall due in order after the deadline"
+            }
+            ContractError::MilestonesPending => "Not every milestone has been approved",
+            ContractError::MilestoneNotDue => "No milestone is ready for a vote",
+            ContractError::NotBacker => "Only backers of the crowdfund may vote on milestones",
+            ContractError::VotingClosed => "No milestone vote is open",
+            ContractError::VotingNotEnded => "Milestone vote has not ended",
+            ContractError::RewardTierNotFound => "Reward tier does not exist",
+            ContractError::RewardTierSoldOut => "Reward tier is sold out",
+            ContractError::PledgeBelowTierMinimum => {
+                "Attached deposit is below the minimum pledge of the reward tier"
+            }
+            ContractError::InvalidRewardTier => {
+                "Reward tier quantity cannot be below the number already claimed"
+            }
+            ContractError::TooManyRewardTiers => "Crowdfund already offers the most reward tiers",
+            ContractError::BadgeNotFound => "Backer badge does not exist",
+            ContractError::NotBadgeOwner => "Only the owner of the badge may transfer it",
+            ContractError::InvalidBadgeReceiver => "Badge owner cannot transfer it to themselves",
+            ContractError::RoundNotFound => "Matching round does not exist",
+            ContractError::RoundClosed => "Matching round is closed",
+            ContractError::RoundNotEnded => "Matching round has not ended",
+            ContractError::RoundFull => "Matching round has no room for more crowdfunds",
+            ContractError::AlreadyInRound => "Crowdfund already joined a matching round",
+            ContractError::InvalidMatchCap => "Match cap must be between 1 and 10000 basis points",
+            ContractError::NoMatch => "Crowdfund has no match to settle in this round",
+            ContractError::MatchPending => "Crowdfund has not succeeded or failed yet",
+            ContractError::NothingToReclaim => "Nothing to reclaim from this matching round",
+            ContractError::StorageNotRegistered => {
+                "Account has no storage deposit, call storage_deposit first"
+            }
+            ContractError::StorageDepositTooSmall => {
+                "Attached deposit is below the minimum storage balance"
+            }
+            ContractError::InsufficientStorage => "Storage deposit does not cover the storage used",
+            ContractError::StorageInUse => "Account still uses storage on this contract",
+            ContractError::NothingToEdit => "Edit does not change any field",
+            ContractError::InvalidTarget => {
+                "Donation target must be above zero and not below the amount raised"
+            }
+            ContractError::NotCancellable => {
+                "Crowdfund is closed or the creator already withdrew funds"
+            }
+            ContractError::StateNotFound => "Contract has no state to migrate",
+            ContractError::UnknownStateVersion => "Contract state has an unknown layout",
+            ContractError::MissingCode => "Upgrade expects the contract wasm as input",
+            ContractError::NotEnoughGas => "Attached gas does not cover the migration",
+            ContractError::InvalidTitle => "Title must be 1 to 100 bytes and not blank",
+            ContractError::InvalidDescription => {
+                "Description must be 1 to 5000 bytes and not blank"
+            }
+            ContractError::InvalidCancelReason => "Cancel reason must be at most 500 bytes",
+            ContractError::InvalidMedia => {
+                "Media must be an http(s) URL or an IPFS CID of at most 512 bytes"
+            }
+            ContractError::PledgeNotFound => "Pledge does not exist",
+            ContractError::NotPledgeDonor => "Only the donor of the pledge may do this",
+            ContractError::PledgeClosed => "Pledge was cancelled or fully released",
+            ContractError::InvalidPledge => {
+                "Pledge needs a positive interval and a deposit covering one instalment"
+            }
+        }
+    }
+
+    pub fn panic(&self) -> ! {
+        env::panic(format!("{}: {}", self.code(), self.message()).as_bytes())
+    }
+}
+
+/// Panics with `error` unless `condition` holds
+pub fn require(condition: bool, error: ContractError) {
+    if !condition {
+        error.panic();
+    }
+}
@@ -0,0 +1,280 @@
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{env, serde_json};
+
+use crate::utils::AccountId;
+
+/// NEP-297 standard name and version reported with every event
+pub const EVENT_STANDARD: &str = "crowdfund";
+pub const EVENT_VERSION: &str = "1.0.0";
+/// Backer badges are reported under the NEP-171 standard so wallets and indexers pick them up
+pub const NFT_EVENT_STANDARD: &str = "nep171";
+pub const NFT_EVENT_VERSION: &str = "1.0.0";
+
+/// State changes reported to indexers. Amounts are in yocto, or in the smallest unit of
+/// `token_id` when one is given
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Event {
+    CrowdfundCreated {
+        crowdfund_id: u64,
+        creator: AccountId,
+        donation_target: U128,
+        deadline: U64,
+    },
+    DonationReceived {
+        crowdfund_id: u64,
+        donation_id: u64,
+        donor: AccountId,
+        amount: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token_id: Option<AccountId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tier_id: Option<u64>,
+    },
+    VoteCast {
+        crowdfund_id: u64,
+        voter: AccountId,
+    },
+    VoteRemoved {
+        crowdfund_id: u64,
+        voter: AccountId,
+    },
+    FundsWithdrawn {
+        crowdfund_id: u64,
+        creator: AccountId,
+        amount: U128,
+        fee: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token_id: Option<AccountId>,
+    },
+    WithdrawalFailed {
+        crowdfund_id: u64,
+        creator: AccountId,
+        amount: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token_id: Option<AccountId>,
+    },
+    RefundIssued {
+        crowdfund_id: u64,
+        donor: AccountId,
+        amount: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token_id: Option<AccountId>,
+    },
+    RefundFailed {
+        crowdfund_id: u64,
+        donor: AccountId,
+        amount: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token_id: Option<AccountId>,
+    },
+    OwnerProposed {
+        owner: AccountId,
+        proposed_owner: AccountId,
+    },
+    OwnershipTransferred {
+        previous_owner: AccountId,
+        owner: AccountId,
+    },
+    ContractPaused {
+        owner: AccountId,
+    },
+    ContractUnpaused {
+        owner: AccountId,
+    },
+    CrowdfundDelisted {
+        crowdfund_id: u64,
+        delisted: bool,
+    },
+    CrowdfundFrozen {
+        crowdfund_id: u64,
+        frozen: bool,
+    },
+    ConfigUpdated {
+        min_donation: U128,
+        max_duration_days: u64,
+        fee_bps: u16,
+    },
+    TreasuryWithdrawn {
+        owner: AccountId,
+        amount: U128,
+    },
+    TreasuryWithdrawalFailed {
+        owner: AccountId,
+        amount: U128,
+    },
+    AcceptedTokenUpdated {
+        token_id: AccountId,
+        accepted: bool,
+    },
+    CrowdfundTokensUpdated {
+        crowdfund_id: u64,
+        token_ids: Vec<AccountId>,
+    },
+    MilestoneVoteStarted {
+        crowdfund_id: u64,
+        milestone: u64,
+        voting_ends_at: U64,
+    },
+    MilestoneVoteCast {
+        crowdfund_id: u64,
+        milestone: u64,
+        voter: AccountId,
+        approve: bool,
+        weight: U128,
+    },
+    MilestoneFinalized {
+        crowdfund_id: u64,
+        milestone: u64,
+        approved: bool,
+    },
+    RewardTierAdded {
+        crowdfund_id: u64,
+        tier_id: u64,
+        min_pledge: U128,
+        quantity: Option<u64>,
+    },
+    RewardTierUpdated {
+        crowdfund_id: u64,
+        tier_id: u64,
+        min_pledge: U128,
+        quantity: Option<u64>,
+    },
+    MatchingRoundCreated {
+        round_id: u64,
+        ends_at: U64,
+        match_cap_bps: u16,
+    },
+    MatchingPoolFunded {
+        round_id: u64,
+        sponsor: AccountId,
+        amount: U128,
+    },
+    MatchingRoundJoined {
+        round_id: u64,
+        crowdfund_id: u64,
+    },
+    MatchCredited {
+        round_id: u64,
+        crowdfund_id: u64,
+        amount: U128,
+    },
+    MatchReturned {
+        round_id: u64,
+        crowdfund_id: u64,
+        amount: U128,
+    },
+    MatchingRoundFinalized {
+        round_id: u64,
+        matched: U128,
+        unallocated: U128,
+    },
+    MatchingFundsReclaimed {
+        round_id: u64,
+        sponsor: AccountId,
+        amount: U128,
+    },
+    StorageDeposited {
+        account_id: AccountId,
+        amount: U128,
+    },
+    StorageWithdrawn {
+        account_id: AccountId,
+        amount: U128,
+    },
+    CrowdfundEdited {
+        crowdfund_id: u64,
+        version: u64,
+        title: String,
+        donation_target: U128,
+    },
+    CampaignCancelled {
+        crowdfund_id: u64,
+        cancelled_by: AccountId,
+        reason: String,
+    },
+    StateMigrated {
+        state_version: u16,
+        crowdfunds: u64,
+        donations: u64,
+    },
+    PledgeCreated {
+        pledge_id: u64,
+        crowdfund_id: u64,
+        donor: AccountId,
+        allowance: U128,
+        instalment: U128,
+        interval: U64,
+    },
+    PledgeInstalmentReleased {
+        pledge_id: u64,
+        crowdfund_id: u64,
+        donation_id: u64,
+        amount: U128,
+    },
+    PledgeClosed {
+        pledge_id: u64,
+        crowdfund_id: u64,
+        donor: AccountId,
+        refunded: U128,
+    },
+}
+
+/// NEP-171 events of the backer badges
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum NftEvent {
+    NftMint(Vec<NftMintData>),
+    NftTransfer(Vec<NftTransferData>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMintData {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTransferData {
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T: Serialize> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a T,
+}
+
+/// Logs the event as `EVENT_JSON:{"standard":..,"version":..,"event":..,"data":..}`
+fn log_event<T: Serialize>(standard: &'static str, version: &'static str, event: &T) {
+    let log = EventLog {
+        standard,
+        version,
+        event,
+    };
+    env::log(format!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap()).as_bytes());
+}
+
+impl Event {
+    pub fn emit(&self) {
+        log_event(EVENT_STANDARD, EVENT_VERSION, self);
+    }
+}
+
+impl NftEvent {
+    pub fn emit(&self) {
+        log_event(NFT_EVENT_STANDARD, NFT_EVENT_VERSION, self);
+    }
+}
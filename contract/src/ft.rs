@@ -0,0 +1,128 @@
+use crate::*;
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::serde::Deserialize;
+use near_sdk::{serde_json, PromiseOrValue};
+
+#[ext_contract(ext_fungible_token)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// `msg` of an `ft_transfer_call` donation, e.g. `{"crowdfund_id": 0}`
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct FtDonationMessage {
+    crowdfund_id: u64,
+    memo: Option<String>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// NEP-141 receiver for token donations. The whole amount goes back to the sender
+    /// when the crowdfund is closed or does not take this token.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: ValidAccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let message: FtDonationMessage = serde_json::from_str(&msg)
+            .unwrap_or_else(|_| ContractError::InvalidTransferMessage.panic());
+        let id = message.crowdfund_id;
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        if self.paused
+            || amount.0 == 0
+            || !self.accepted_tokens.contains(&token_id)
+            || !crowdfund.accepts_token(&token_id)
+            || !crowdfund.is_accepting()
+        {
+            return PromiseOrValue::Value(amount);
+        }
+
+        crowdfund.token_escrow_mut(&token_id).total_donations += amount.0;
+        self.crowdfunds.replace(id, &crowdfund);
+        let donor: AccountId = sender_id.into();
+        let donation_id = self.internal_add_donation(Donation::new_in_token(
+            id,
+            donor.clone(),
+            token_id.clone(),
+            amount.0,
+            message.memo,
+        ));
+        self.internal_mint_badge(&crowdfund, donation_id);
+        // panicking here makes the token contract send the whole amount back
+        self.internal_charge_storage(&donor, initial_storage);
+        Event::DonationReceived {
+            crowdfund_id: id,
+            donation_id,
+            donor,
+            amount,
+            token_id: Some(token_id),
+            tier_id: None,
+        }
+        .emit();
+        PromiseOrValue::Value(U128(0))
+    }
+
+    /// Replaces the tokens a crowdfund takes donations in, all of them must be whitelisted.
+    /// Each token's share of its `target` counts towards the crowdfund's success, so a
+    /// token's target is fixed once it received donations
+    pub fn set_crowdfund_tokens(&mut self, id: u64, tokens: Vec<TokenTarget>) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_creator();
+        crowdfund.assert_status(CrowdfundStatus::Active, ContractError::CampaignClosed);
+        let initial_storage = env::storage_usage();
+        for token in &tokens {
+            require(
+                self.accepted_tokens.contains(&token.token_id),
+                ContractError::TokenNotAccepted,
+            );
+            require(token.target.0 > 0, ContractError::InvalidTarget);
+            let escrow = crowdfund.token_escrow_mut(&token.token_id);
+            require(
+                escrow.total_donations == 0 || escrow.target == token.target.0,
+                ContractError::TokenTargetFixed,
+            );
+            escrow.target = token.target.0;
+        }
+        let token_ids: Vec<AccountId> = tokens.into_iter().map(|token| token.token_id).collect();
+        crowdfund.accepted_tokens = token_ids.clone();
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&crowdfund.creator, initial_storage);
+        Event::CrowdfundTokensUpdated {
+            crowdfund_id: id,
+            token_ids,
+        }
+        .emit();
+    }
+
+    /// Sends the escrowed donations in `token_id` to the creator of a successful crowdfund
+    pub fn withdraw_token(&mut self, id: u64, token_id: AccountId) -> Promise {
+        self.assert_not_paused();
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        crowdfund.assert_creator();
+        require(crowdfund.is_successful(), ContractError::TargetNotReached);
+        // tokens are not split by milestone, they are held until every milestone is approved
+        require(
+            crowdfund.milestones_approved(),
+            ContractError::MilestonesPending,
+        );
+        self.internal_withdraw(crowdfund, Some(token_id))
+    }
+
+    pub fn get_accepted_tokens(&self) -> Vec<AccountId> {
+        self.accepted_tokens.to_vec()
+    }
+
+    pub fn get_crowdfund_tokens(&self, id: u64) -> Vec<TokenEscrowView> {
+        self.internal_get_crowdfund(id)
+            .token_escrows
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+}
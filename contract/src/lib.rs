@@ -11,93 +11,955 @@
  *
  */
 
+mod admin;
+mod editing;
+mod errors;
+mod events;
+mod ft;
+mod matching;
+mod migration;
+mod milestones;
 mod models;
+mod nft;
+mod pledges;
+mod rewards;
+mod storage;
 mod utils;
+mod views;
 use crate::{
-    models::{Crowdfund, Donation},
-    utils::{assert_self, assert_single_promise_success, AccountId, ONE_NEAR},
+    errors::{require, ContractError},
+    events::Event,
+    ft::ext_fungible_token,
+    models::{
+        Crowdfund, CrowdfundRevision, CrowdfundSort, CrowdfundStatus, Donation, MatchingRound,
+        Milestone, MilestoneInput, MilestoneStatus, PlatformConfig, PlatformStats, Pledge,
+        RewardTier, RewardTierInput, RoundEntry, RoundSponsor, TokenEscrow, TokenTarget,
+    },
+    nft::{Badge, TokenId},
+    storage::StorageAccount,
+    utils::{
+        assert_one_yocto, assert_self, integer_sqrt, is_promise_success, toYocto, AccountId,
+        Balance, Money, Timestamp, BASIS_POINTS, FT_TRANSFER_GAS, MATCH_PRECISION,
+        MAX_CANCEL_REASON_LENGTH, MAX_DURATION_DAYS, MAX_MILESTONES, MAX_REWARD_TIERS,
+        MAX_ROUND_CROWDFUNDS, MILESTONE_QUORUM_BPS, MILESTONE_VOTING_PERIOD, NFT_ON_TRANSFER_GAS,
+        ONE_DAY, PLEDGE_BATCH_LIMIT, PLEDGE_GAS_RESERVE, PLEDGE_INSTALMENT_BYTES, QF_UNIT,
+        REFUND_BATCH_LIMIT, REFUND_GAS_RESERVE, STATE_VERSION, STORAGE_REGISTRATION_BYTES,
+        UPGRADE_GAS, XCC_GAS,
+    },
+    views::{
+        ContractStats, CrowdfundPayout, CrowdfundRevisionView, CrowdfundSummary, CrowdfundView,
+        DonationView, MatchingRoundView, MilestoneView, PlatformConfigView, PledgeView,
+        RewardTierView, TokenEscrowView,
+    },
 };
 
 // To conserve gas, efficient serialization is achieved through Borsh (http://borsh.io/)
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, LookupSet, TreeMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::{U128, U64};
 #[allow(unused_imports)]
-use near_sdk::{env, near_bindgen, PromiseIndex};
+use near_sdk::{
+    env, ext_contract, near_bindgen, BorshStorageKey, PanicOnDefault, Promise, PromiseIndex,
+};
 near_sdk::setup_alloc!();
 
+#[ext_contract(ext_self)]
+pub trait SelfCallbacks {
+    fn on_withdraw(&mut self, id: u64, amount: U128, fee: U128, token_id: Option<AccountId>);
+    fn on_withdraw_treasury(&mut self, amount: U128);
+    fn on_refund(
+        &mut self,
+        id: u64,
+        donation_ids: Vec<u64>,
+        amount: U128,
+        token_id: Option<AccountId>,
+    );
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+    ) -> bool;
+}
+
+/// Prefixes of the persistent collections, nested ones are keyed by their owner
+#[derive(BorshStorageKey, BorshSerialize)]
+pub enum StorageKey {
+    Crowdfunds,
+    CrowdfundsByCreator,
+    CreatorCrowdfunds { account_hash: Vec<u8> },
+    Donations,
+    DonationsByCrowdfund,
+    CrowdfundDonations { crowdfund_id: u64 },
+    DonationsByDonor,
+    DonorDonations { account_hash: Vec<u8> },
+    VotesByCrowdfund,
+    CrowdfundVotes { crowdfund_id: u64 },
+    AcceptedTokens,
+    MilestoneVotes,
+    Badges,
+    BadgesByOwner,
+    OwnerBadges { account_hash: Vec<u8> },
+    MatchingRounds,
+    RoundContributions,
+    StorageAccounts,
+    RevisionsByCrowdfund,
+    CrowdfundRevisions { crowdfund_id: u64 },
+    Pledges,
+    PledgeSchedule,
+    PledgesByDonor,
+    DonorPledges { account_hash: Vec<u8> },
+    RoundSponsors,
+    DonationsByTier,
+    TierDonations { crowdfund_id: u64, tier_id: u64 },
+}
+
 #[near_bindgen]
-#[derive(Clone, Default, BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
+    /// layout of this struct, `migrate` converts older ones
+    state_version: u16,
     owner: AccountId,
-    crowdfunds: Vec<Crowdfund>,
-    donations: Vec<Donation>,
+    /// account that may accept ownership, see `propose_owner`
+    proposed_owner: Option<AccountId>,
+    paused: bool,
+    config: PlatformConfig,
+    crowdfunds: Vector<Crowdfund>,
+    /// crowdfund ids of every creator, in creation order
+    crowdfunds_by_creator: LookupMap<AccountId, Vector<u64>>,
+    donations: Vector<Donation>,
+    /// donation ids of every crowdfund, in donation order
+    donations_by_crowdfund: LookupMap<u64, Vector<u64>>,
+    /// donation ids of every donor, in donation order
+    donations_by_donor: LookupMap<AccountId, Vector<u64>>,
+    /// donation ids that selected each reward tier, keyed by (crowdfund, tier)
+    donations_by_tier: LookupMap<(u64, u64), Vector<u64>>,
+    /// accounts that voted for every crowdfund, at most one vote each
+    votes_by_crowdfund: LookupMap<u64, UnorderedSet<AccountId>>,
+    /// NEP-141 tokens the owner allows crowdfunds to take donations in
+    accepted_tokens: UnorderedSet<AccountId>,
+    /// platform fees collected from creator withdrawals, in yocto
+    treasury: u128,
+    /// totals read by `get_contract_stats`
+    stats: PlatformStats,
+    /// (crowdfund id, milestone index, backer) of every milestone vote cast
+    milestone_votes: LookupSet<(u64, u64, AccountId)>,
+    /// NEP-171 backer badges, one per donation
+    badges: UnorderedMap<TokenId, Badge>,
+    badges_by_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    /// quadratic funding rounds, in creation order
+    matching_rounds: Vector<MatchingRound>,
+    /// NEAR each backer gave to a crowdfund during a round, keyed by (round, crowdfund, backer)
+    round_contributions: LookupMap<(u64, u64, AccountId), Money>,
+    /// what each sponsor put into a round's pool, keyed by (round, sponsor)
+    round_sponsors: LookupMap<(u64, AccountId), RoundSponsor>,
+    /// NEP-145 storage deposits, every account pays for the state it adds
+    storage_accounts: LookupMap<AccountId, StorageAccount>,
+    /// replaced versions of every edited crowdfund, oldest first
+    revisions_by_crowdfund: LookupMap<u64, Vector<CrowdfundRevision>>,
+    /// recurring donations, in creation order
+    pledges: Vector<Pledge>,
+    /// (next due time, pledge id) of the pledges with instalments left, earliest first, so
+    /// `process_due_pledges` reads only what is due
+    pledge_schedule: TreeMap<(Timestamp, u64), ()>,
+    /// pledge ids of every donor, in creation order
+    pledges_by_donor: LookupMap<AccountId, Vector<u64>>,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
     pub fn init(owner: AccountId) -> Self {
-        let crowdfunds: Vec<Crowdfund> = Vec::new();
-        let donations: Vec<Donation> = Vec::new();
+        require(!env::state_exists(), ContractError::AlreadyInitialized);
+        Contract::internal_new(owner)
+    }
+
+    /// `donation_target` is the campaign target in yoctoNEAR, the unit donations come in.
+    /// With `milestones` the NEAR raised is released to the creator one milestone at a time
+    pub fn add_crowdfund(
+        &mut self,
+        title: String,
+        donation_target: U128,
+        description: String,
+        duration_days: u64,
+        milestones: Option<Vec<MilestoneInput>>,
+        media: Option<String>,
+    ) {
+        self.assert_not_paused();
+        let initial_storage = env::storage_usage();
+        require(
+            duration_days > 0 && duration_days <= self.config.max_duration_days,
+            ContractError::InvalidDuration,
+        );
+        let id = self.crowdfunds.len();
+        let deadline = env::block_timestamp() + duration_days * ONE_DAY;
+        let milestones = self.internal_milestones(milestones.unwrap_or_default(), deadline);
+        let mut crowdfund = Crowdfund::new(
+            id,
+            title,
+            donation_target.0,
+            description,
+            deadline,
+            milestones,
+        );
+        crowdfund.media = media;
+        crowdfund.assert_valid();
+        self.internal_add_crowdfund(&crowdfund);
+        self.internal_charge_storage(&crowdfund.creator, initial_storage);
+        Event::CrowdfundCreated {
+            crowdfund_id: id,
+            creator: crowdfund.creator,
+            donation_target: U128(crowdfund.donation_target),
+            deadline: U64(deadline),
+        }
+        .emit();
+    }
+
+    pub fn list_crowdfunds(&self, from_index: u64, limit: u64) -> Vec<CrowdfundSummary> {
+        self.internal_crowdfund_window(from_index, limit)
+            .map(|crowdfund| self.internal_summary(&crowdfund))
+            .collect()
+    }
+
+    pub fn list_crowdfunds_by_creator(
+        &self,
+        account_id: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<CrowdfundSummary> {
+        let crowdfund_ids = match self.crowdfunds_by_creator.get(&account_id) {
+            Some(crowdfund_ids) => crowdfund_ids,
+            None => return vec![],
+        };
+        let to_index = std::cmp::min(from_index.saturating_add(limit), crowdfund_ids.len());
+        (from_index..to_index)
+            .map(|index| self.internal_get_crowdfund(crowdfund_ids.get(index).unwrap()))
+            .filter(|crowdfund| !crowdfund.delisted)
+            .map(|crowdfund| self.internal_summary(&crowdfund))
+            .collect()
+    }
+
+    /// Status depends on the block time, so this filters the crowdfunds with ids in
+    /// `from_index..from_index + limit` instead of using an index. A page may hold fewer
+    /// than `limit` results, the next one starts at `from_index + limit`
+    pub fn list_crowdfunds_by_status(
+        &self,
+        status: CrowdfundStatus,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<CrowdfundSummary> {
+        self.internal_crowdfund_window(from_index, limit)
+            .filter(|crowdfund| crowdfund.status() == status)
+            .map(|crowdfund| self.internal_summary(&crowdfund))
+            .collect()
+    }
+
+    /// Crowdfunds that raised at least `min_percent` of their donation target, among the
+    /// ids in `from_index..from_index + limit` like `list_crowdfunds_by_status`
+    pub fn list_crowdfunds_by_progress(
+        &self,
+        min_percent: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<CrowdfundSummary> {
+        self.internal_crowdfund_window(from_index, limit)
+            .filter(|crowdfund| crowdfund.progress() >= min_percent as u128)
+            .map(|crowdfund| self.internal_summary(&crowdfund))
+            .collect()
+    }
+
+    /// Newest first pages through every crowdfund. The other orders sort the ids in
+    /// `from_index..from_index + limit`, so one call never reads more than `limit` crowdfunds
+    pub fn list_crowdfunds_sorted(
+        &self,
+        sort_by: CrowdfundSort,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<CrowdfundSummary> {
+        if sort_by == CrowdfundSort::CreatedAt {
+            let count = self.crowdfunds.len();
+            let to_index = std::cmp::min(from_index.saturating_add(limit), count);
+            return (from_index..to_index)
+                .map(|index| self.internal_get_crowdfund(count - 1 - index))
+                .filter(|crowdfund| !crowdfund.delisted)
+                .map(|crowdfund| self.internal_summary(&crowdfund))
+                .collect();
+        }
+        let mut summaries: Vec<CrowdfundSummary> = self
+            .internal_crowdfund_window(from_index, limit)
+            .map(|crowdfund| self.internal_summary(&crowdfund))
+            .collect();
+        match sort_by {
+            CrowdfundSort::CreatedAt => {}
+            CrowdfundSort::TotalVotes => {
+                summaries.sort_by(|a, b| b.total_votes.cmp(&a.total_votes))
+            }
+            CrowdfundSort::TotalDonations => {
+                summaries.sort_by(|a, b| b.total_donations.0.cmp(&a.total_donations.0))
+            }
+        }
+        summaries
+    }
+
+    pub fn add_vote(&mut self, id: u64) {
+        self.assert_not_paused();
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_accepting();
+        let mut votes = self.votes_by_crowdfund.get(&id).unwrap();
+        let voter = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        require(votes.insert(&voter), ContractError::AlreadyVoted);
+        self.votes_by_crowdfund.insert(&id, &votes);
+        self.internal_charge_storage(&voter, initial_storage);
+        Event::VoteCast {
+            crowdfund_id: id,
+            voter,
+        }
+        .emit();
+    }
+
+    pub fn remove_vote(&mut self, id: u64) {
+        self.assert_not_paused();
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_accepting();
+        let mut votes = self.votes_by_crowdfund.get(&id).unwrap();
+        let voter = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        require(votes.remove(&voter), ContractError::NotVoted);
+        self.votes_by_crowdfund.insert(&id, &votes);
+        self.internal_charge_storage(&voter, initial_storage);
+        Event::VoteRemoved {
+            crowdfund_id: id,
+            voter,
+        }
+        .emit();
+    }
+
+    pub fn has_voted(&self, id: u64, account_id: AccountId) -> bool {
+        match self.votes_by_crowdfund.get(&id) {
+            Some(votes) => votes.contains(&account_id),
+            None => false,
+        }
+    }
+
+    #[payable]
+    /// `tier_id` selects a reward tier, the deposit must cover its minimum pledge
+    pub fn add_donation(&mut self, id: u64, memo: Option<String>, tier_id: Option<u64>) {
+        self.assert_not_paused();
+        let amount = env::attached_deposit();
+        require(
+            amount > 0 && amount >= self.config.min_donation,
+            ContractError::DepositTooSmall,
+        );
+        let initial_storage = env::storage_usage();
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_accepting();
+        let donor = env::predecessor_account_id();
+        self.internal_donate(
+            crowdfund,
+            Donation::new(id, donor.clone(), amount, memo, tier_id),
+        );
+        // the deposit is the donation, so the records it adds are paid from storage_deposit
+        self.internal_charge_storage(&donor, initial_storage);
+    }
+
+    /// Sends the escrowed donations of a crowdfund to its creator once the target is reached,
+    /// or the share of approved milestones when the crowdfund releases funds by milestone
+    pub fn withdraw(&mut self, id: u64) -> Promise {
+        self.assert_not_paused();
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        crowdfund.assert_creator();
+        require(
+            crowdfund.is_successful() || crowdfund.milestone_rejected,
+            ContractError::TargetNotReached,
+        );
+        self.internal_withdraw(crowdfund, None)
+    }
+
+    /// Stops a crowdfund for good, the creator or the contract owner may do this until the
+    /// creator withdrew funds. Donations and votes are closed and every donor can be refunded.
+    /// The caller pays for storing `reason`
+    pub fn cancel_crowdfund(&mut self, id: u64, reason: String) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        let caller = env::predecessor_account_id();
+        require(
+            caller == crowdfund.creator || caller == self.owner,
+            ContractError::NotCreator,
+        );
+        require(
+            reason.len() <= MAX_CANCEL_REASON_LENGTH,
+            ContractError::InvalidCancelReason,
+        );
+        crowdfund.assert_not_frozen();
+        require(crowdfund.is_cancellable(), ContractError::NotCancellable);
+        let initial_storage = env::storage_usage();
+        crowdfund.cancelled = true;
+        crowdfund.cancel_reason = Some(reason.clone());
+        if crowdfund.matched > 0 {
+            // donors are refunded what they gave, the match goes back where it came from
+            let matched = crowdfund.matched;
+            crowdfund.total_donations -= matched;
+            crowdfund.matched = 0;
+            self.stats.total_raised -= matched;
+            self.internal_return_match(crowdfund.matching_round.unwrap(), id, matched);
+        }
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&caller, initial_storage);
+        Event::CampaignCancelled {
+            crowdfund_id: id,
+            cancelled_by: caller,
+            reason,
+        }
+        .emit();
+    }
+
+    /// Callback for withdrawals, moves the fee to the treasury or restores the escrow
+    /// if the transfer failed
+    pub fn on_withdraw(&mut self, id: u64, amount: U128, fee: U128, token_id: Option<AccountId>) {
+        assert_self();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        if is_promise_success() {
+            self.treasury += fee.0;
+            Event::FundsWithdrawn {
+                crowdfund_id: id,
+                creator: crowdfund.creator,
+                amount: U128(amount.0 - fee.0),
+                fee,
+                token_id,
+            }
+            .emit();
+            return;
+        }
+        crowdfund.revert_withdrawal(&token_id, amount.0);
+        crowdfund.fees -= fee.0;
+        if token_id.is_none() {
+            self.stats.total_withdrawn -= amount.0;
+        }
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(id, &crowdfund);
+        Event::WithdrawalFailed {
+            crowdfund_id: id,
+            creator: crowdfund.creator,
+            amount,
+            token_id,
+        }
+        .emit();
+    }
+
+    /// Pays the caller back everything they donated to a failed or cancelled crowdfund,
+    /// in NEAR or, when `token_id` is given, in that fungible token
+    pub fn claim_refund(&mut self, id: u64, token_id: Option<AccountId>) -> Promise {
+        self.assert_not_paused();
+        let donor = env::predecessor_account_id();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        require(crowdfund.is_refundable(), ContractError::NotRefundable);
+
+        let mut donation_ids: Vec<u64> = Vec::new();
+        let mut amount: u128 = 0;
+        let mut donated: u128 = 0;
+        if let Some(donor_donations) = self.donations_by_donor.get(&donor) {
+            for donation_id in donor_donations.iter() {
+                let mut donation = self.donations.get(donation_id).unwrap();
+                if donation.crowdfund_id == id
+                    && donation.token_id == token_id
+                    && !donation.refunded
+                {
+                    donation.refunded = true;
+                    amount += crowdfund.refund_for(&token_id, donation.amount);
+                    donated += donation.amount;
+                    donation_ids.push(donation_id);
+                    self.donations.replace(donation_id, &donation);
+                }
+            }
+        }
+        require(amount > 0, ContractError::NothingToRefund);
+        crowdfund.record_refund(&token_id, amount);
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(id, &crowdfund);
+        if token_id.is_none() {
+            self.stats.total_refunded += amount;
+            self.internal_remove_round_contribution(&crowdfund, &donor, donated);
+        }
+
+        internal_transfer(donor, &token_id, amount).then(ext_self::on_refund(
+            id,
+            donation_ids,
+            U128(amount),
+            token_id,
+            &env::current_account_id(),
+            0,
+            XCC_GAS,
+        ))
+    }
+
+    /// Pushes refunds for up to `limit` outstanding donations of a failed or cancelled crowdfund.
+    /// Transfers that fail are restored to the ledger and left for the donor to `claim_refund`
+    pub fn process_refunds(&mut self, id: u64, limit: u64) -> u64 {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        require(crowdfund.is_refundable(), ContractError::NotRefundable);
+
+        let crowdfund_donations = self.donations_by_crowdfund.get(&id).unwrap();
+        let limit = std::cmp::min(limit, REFUND_BATCH_LIMIT);
+        let mut processed: u64 = 0;
+        while processed < limit && crowdfund.refund_cursor < crowdfund_donations.len() {
+            let donation_id = crowdfund_donations.get(crowdfund.refund_cursor).unwrap();
+            let mut donation = self.donations.get(donation_id).unwrap();
+            if donation.refunded {
+                crowdfund.refund_cursor += 1;
+                continue;
+            }
+            // stop once the attached gas cannot pay for another transfer and its callback,
+            // the next call picks up from the cursor
+            let refund_gas = match donation.token_id {
+                Some(_) => FT_TRANSFER_GAS + XCC_GAS,
+                None => XCC_GAS,
+            };
+            let remaining_gas = env::prepaid_gas().saturating_sub(env::used_gas());
+            if remaining_gas < refund_gas + REFUND_GAS_RESERVE {
+                break;
+            }
+            crowdfund.refund_cursor += 1;
+            donation.refunded = true;
+            self.donations.replace(donation_id, &donation);
+            let amount = crowdfund.refund_for(&donation.token_id, donation.amount);
+            crowdfund.record_refund(&donation.token_id, amount);
+            if donation.token_id.is_none() {
+                self.stats.total_refunded += amount;
+                self.internal_remove_round_contribution(
+                    &crowdfund,
+                    &donation.donor,
+                    donation.amount,
+                );
+            }
+            processed += 1;
+
+            internal_transfer(donation.donor, &donation.token_id, amount).then(
+                ext_self::on_refund(
+                    id,
+                    vec![donation_id],
+                    U128(amount),
+                    donation.token_id,
+                    &env::current_account_id(),
+                    0,
+                    XCC_GAS,
+                ),
+            );
+        }
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(id, &crowdfund);
+        processed
+    }
+
+    /// Callback for refunds, restores the ledger entries if the transfer failed
+    pub fn on_refund(
+        &mut self,
+        id: u64,
+        donation_ids: Vec<u64>,
+        amount: U128,
+        token_id: Option<AccountId>,
+    ) {
+        assert_self();
+        // every refund pays a single donor, so the first entry names them
+        let donor = self.donations.get(donation_ids[0]).unwrap().donor;
+        if is_promise_success() {
+            Event::RefundIssued {
+                crowdfund_id: id,
+                donor,
+                amount,
+                token_id,
+            }
+            .emit();
+            return;
+        }
+        for donation_id in donation_ids {
+            let mut donation = self.donations.get(donation_id).unwrap();
+            donation.refunded = false;
+            self.donations.replace(donation_id, &donation);
+        }
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.revert_refund(&token_id, amount.0);
+        if token_id.is_none() {
+            self.stats.total_refunded -= amount.0;
+        }
+        self.crowdfunds.replace(id, &crowdfund);
+        Event::RefundFailed {
+            crowdfund_id: id,
+            donor,
+            amount,
+            token_id,
+        }
+        .emit();
+    }
+
+    pub fn get_donations_for_crowdfund(
+        &self,
+        id: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<DonationView> {
+        match self.donations_by_crowdfund.get(&id) {
+            Some(donation_ids) => self.internal_donations_page(&donation_ids, from_index, limit),
+            None => vec![],
+        }
+    }
+
+    pub fn get_donations_by_donor(
+        &self,
+        account_id: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<DonationView> {
+        match self.donations_by_donor.get(&account_id) {
+            Some(donation_ids) => self.internal_donations_page(&donation_ids, from_index, limit),
+            None => vec![],
+        }
+    }
+
+    pub fn get_crowdfund_payout(&self, id: u64) -> CrowdfundPayout {
+        self.internal_get_crowdfund(id).payout(&self.config)
+    }
+
+    pub fn get_crowdfund_status(&self, id: u64) -> CrowdfundStatus {
+        self.internal_get_crowdfund(id).status()
+    }
+
+    pub fn crowdfund_count(&self) -> u64 {
+        self.crowdfunds.len()
+    }
+
+    pub fn get_total_donations(&self, id: u64) -> U128 {
+        let crowdfund = self.internal_get_crowdfund(id);
+        U128(crowdfund.total_donations)
+    }
+
+    pub fn get_crowdfund(&self, id: u64) -> CrowdfundView {
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.view(self.internal_total_votes(id))
+    }
+
+    pub fn get_crowdfund_summary(&self, id: u64) -> CrowdfundSummary {
+        self.internal_summary(&self.internal_get_crowdfund(id))
+    }
+
+    /// Share of the donation target raised so far, in percent, above 100 once overfunded
+    pub fn get_progress(&self, id: u64) -> u64 {
+        self.internal_get_crowdfund(id).progress() as u64
+    }
+
+    /// Reads running totals only. Crowdfunds are recounted as active or successful when a
+    /// transaction touches them, one that ended unnoticed still counts as active
+    pub fn get_contract_stats(&self) -> ContractStats {
+        ContractStats {
+            crowdfunds: self.crowdfunds.len(),
+            active_crowdfunds: self.stats.active_crowdfunds,
+            successful_crowdfunds: self.stats.successful_crowdfunds,
+            donations: self.donations.len(),
+            total_raised: U128(self.stats.total_raised),
+            total_withdrawn: U128(self.stats.total_withdrawn),
+            total_refunded: U128(self.stats.total_refunded),
+            treasury: U128(self.treasury),
+        }
+    }
+}
+
+/// Sends `amount` of NEAR, or of the fungible token `token_id`, to `receiver_id`
+fn internal_transfer(
+    receiver_id: AccountId,
+    token_id: &Option<AccountId>,
+    amount: u128,
+) -> Promise {
+    match token_id {
+        None => Promise::new(receiver_id).transfer(amount),
+        Some(token_id) => ext_fungible_token::ft_transfer(
+            receiver_id,
+            U128(amount),
+            None,
+            token_id,
+            1,
+            FT_TRANSFER_GAS,
+        ),
+    }
+}
 
+impl Contract {
+    fn internal_new(owner: AccountId) -> Self {
         Contract {
+            state_version: STATE_VERSION,
             owner,
-            crowdfunds,
-            donations,
+            proposed_owner: None,
+            paused: false,
+            config: PlatformConfig::default(),
+            crowdfunds: Vector::new(StorageKey::Crowdfunds),
+            crowdfunds_by_creator: LookupMap::new(StorageKey::CrowdfundsByCreator),
+            donations: Vector::new(StorageKey::Donations),
+            donations_by_crowdfund: LookupMap::new(StorageKey::DonationsByCrowdfund),
+            donations_by_donor: LookupMap::new(StorageKey::DonationsByDonor),
+            donations_by_tier: LookupMap::new(StorageKey::DonationsByTier),
+            votes_by_crowdfund: LookupMap::new(StorageKey::VotesByCrowdfund),
+            accepted_tokens: UnorderedSet::new(StorageKey::AcceptedTokens),
+            treasury: 0,
+            stats: PlatformStats::default(),
+            milestone_votes: LookupSet::new(StorageKey::MilestoneVotes),
+            badges: UnorderedMap::new(StorageKey::Badges),
+            badges_by_owner: LookupMap::new(StorageKey::BadgesByOwner),
+            matching_rounds: Vector::new(StorageKey::MatchingRounds),
+            round_contributions: LookupMap::new(StorageKey::RoundContributions),
+            round_sponsors: LookupMap::new(StorageKey::RoundSponsors),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            revisions_by_crowdfund: LookupMap::new(StorageKey::RevisionsByCrowdfund),
+            pledges: Vector::new(StorageKey::Pledges),
+            pledge_schedule: TreeMap::new(StorageKey::PledgeSchedule),
+            pledges_by_donor: LookupMap::new(StorageKey::PledgesByDonor),
         }
     }
 
-    pub fn add_crowdfund(&mut self, title: String, donate: u128, description: String) {
-        let id = self.crowdfunds.len() as i32;
+    fn internal_get_crowdfund(&self, id: u64) -> Crowdfund {
         self.crowdfunds
-            .push(Crowdfund::new(id, title, donate, description));
-        env::log("Added a new crowdfund".as_bytes());
+            .get(id)
+            .unwrap_or_else(|| ContractError::CrowdfundNotFound.panic())
     }
 
-    pub fn list_crowdfunds(&self) -> Vec<Crowdfund> {
-        // assert_self();
-        let crowdfunds = &self.crowdfunds;
-        return crowdfunds.to_vec();
+    /// Pays out what the creator has not withdrawn yet in NEAR or in one token
+    fn internal_withdraw(
+        &mut self,
+        mut crowdfund: Crowdfund,
+        token_id: Option<AccountId>,
+    ) -> Promise {
+        let amount = crowdfund.withdrawable(&token_id);
+        require(amount > 0, ContractError::NothingToWithdraw);
+        // the platform fee is only charged on NEAR, tokens are paid out in full
+        let fee = match token_id {
+            None => self.config.fee(amount),
+            Some(_) => 0,
+        };
+        crowdfund.record_withdrawal(&token_id, amount);
+        crowdfund.fees += fee;
+        if token_id.is_none() {
+            self.stats.total_withdrawn += amount;
+        }
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(crowdfund.id, &crowdfund);
+
+        internal_transfer(crowdfund.creator, &token_id, amount - fee).then(ext_self::on_withdraw(
+            crowdfund.id,
+            U128(amount),
+            U128(fee),
+            token_id,
+            &env::current_account_id(),
+            0,
+            XCC_GAS,
+        ))
     }
 
-    pub fn add_vote(&mut self, id: usize) {
-        let crowdfund: &mut Crowdfund = self.crowdfunds.get_mut(id).unwrap();
-        let voter = env::predecessor_account_id();
-        crowdfund.total_votes += 1;
-        env::log("vote submitted succesfully".as_bytes());
-        crowdfund.votes.push(voter);
+    /// Recounts the crowdfund in the stats if its status changed since it was last counted.
+    /// Status follows the clock, so this runs whenever a transaction touches a crowdfund
+    /// that may have ended
+    pub(crate) fn internal_record_status(&mut self, crowdfund: &mut Crowdfund) {
+        let status = crowdfund.status();
+        if status != crowdfund.counted_status {
+            self.stats.record_status(crowdfund.counted_status, status);
+            crowdfund.counted_status = status;
+        }
+    }
+
+    fn internal_total_votes(&self, id: u64) -> u64 {
+        self.votes_by_crowdfund
+            .get(&id)
+            .map_or(0, |votes| votes.len())
+    }
+
+    /// Listed crowdfunds with ids in `from_index..from_index + limit`, listings read no
+    /// more than this window so their gas does not grow with the number of crowdfunds
+    fn internal_crowdfund_window(
+        &self,
+        from_index: u64,
+        limit: u64,
+    ) -> impl Iterator<Item = Crowdfund> + '_ {
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.crowdfunds.len());
+        (from_index..to_index)
+            .map(move |id| self.internal_get_crowdfund(id))
+            .filter(|crowdfund| !crowdfund.delisted)
+    }
+
+    fn internal_summary(&self, crowdfund: &Crowdfund) -> CrowdfundSummary {
+        crowdfund.summary(self.internal_total_votes(crowdfund.id))
     }
 
-    // maybe not need to pass amount, just use env::attached_deposit instead
-    pub fn add_donation(&mut self, id: usize, amount: u128) {
-        let transfer_amount: u128 = ONE_NEAR * amount;
-        let crowdfund: &mut Crowdfund = self.crowdfunds.get_mut(id).unwrap();
-        crowdfund.total_donations = crowdfund.total_donations + transfer_amount;
-        self.donations.push(Donation::new());
+    /// Appends a crowdfund and sets up its donation, vote and creator indices
+    fn internal_add_crowdfund(&mut self, crowdfund: &Crowdfund) {
+        let id = crowdfund.id;
+        self.crowdfunds.push(crowdfund);
+        self.stats.active_crowdfunds += 1;
+        self.donations_by_crowdfund.insert(
+            &id,
+            &Vector::new(StorageKey::CrowdfundDonations { crowdfund_id: id }),
+        );
+        self.votes_by_crowdfund.insert(
+            &id,
+            &UnorderedSet::new(StorageKey::CrowdfundVotes { crowdfund_id: id }),
+        );
+
+        let creator = &crowdfund.creator;
+        let mut creator_crowdfunds = self.crowdfunds_by_creator.get(creator).unwrap_or_else(|| {
+            Vector::new(StorageKey::CreatorCrowdfunds {
+                account_hash: env::sha256(creator.as_bytes()),
+            })
+        });
+        creator_crowdfunds.push(&id);
+        self.crowdfunds_by_creator
+            .insert(creator, &creator_crowdfunds);
+    }
 
-        println!("transfer_amount: {}", transfer_amount);
-        println!("amount: {}", amount);
-        println!("attached_deposit: {}", env::attached_deposit());
-        near_sdk::Promise::new(env::predecessor_account_id()).transfer(transfer_amount);
-        env::log("You have donated succesfully".as_bytes());
+    /// Puts a NEAR donation into the escrow of an accepting crowdfund, mints its badge and
+    /// counts it towards the crowdfund's matching round. Returns the donation id
+    fn internal_donate(&mut self, mut crowdfund: Crowdfund, donation: Donation) -> u64 {
+        let id = crowdfund.id;
+        let amount = donation.amount;
+        let donor = donation.donor.clone();
+        let tier_id = donation.tier_id;
+        if let Some(tier_id) = tier_id {
+            crowdfund.claim_reward_tier(tier_id, amount);
+        }
+        // the deposit stays on the contract account as escrow for the campaign
+        crowdfund.total_donations += amount;
+        self.stats.total_raised += amount;
+        self.crowdfunds.replace(id, &crowdfund);
+        let donation_id = self.internal_add_donation(donation);
+        self.internal_mint_badge(&crowdfund, donation_id);
+        self.internal_record_round_contribution(&crowdfund, &donor, amount);
+        Event::DonationReceived {
+            crowdfund_id: id,
+            donation_id,
+            donor,
+            amount: U128(amount),
+            token_id: None,
+            tier_id,
+        }
+        .emit();
+        donation_id
     }
 
-    pub fn crowdfund_count(&mut self) -> usize {
-        return self.crowdfunds.len();
+    /// Appends a donation to the ledger and to the crowdfund, reward tier and donor indices
+    fn internal_add_donation(&mut self, donation: Donation) -> u64 {
+        let donation_id = self.donations.len();
+        self.donations.push(&donation);
+
+        // migrated donations are not linked to a crowdfund and only go to the donor index
+        if let Some(mut crowdfund_donations) =
+            self.donations_by_crowdfund.get(&donation.crowdfund_id)
+        {
+            crowdfund_donations.push(&donation_id);
+            self.donations_by_crowdfund
+                .insert(&donation.crowdfund_id, &crowdfund_donations);
+        }
+
+        if let Some(tier_id) = donation.tier_id {
+            let key = (donation.crowdfund_id, tier_id);
+            let mut tier_donations = self.donations_by_tier.get(&key).unwrap_or_else(|| {
+                Vector::new(StorageKey::TierDonations {
+                    crowdfund_id: donation.crowdfund_id,
+                    tier_id,
+                })
+            });
+            tier_donations.push(&donation_id);
+            self.donations_by_tier.insert(&key, &tier_donations);
+        }
+
+        let mut donor_donations =
+            self.donations_by_donor
+                .get(&donation.donor)
+                .unwrap_or_else(|| {
+                    Vector::new(StorageKey::DonorDonations {
+                        account_hash: env::sha256(donation.donor.as_bytes()),
+                    })
+                });
+        donor_donations.push(&donation_id);
+        self.donations_by_donor
+            .insert(&donation.donor, &donor_donations);
+        donation_id
     }
 
-    pub fn get_total_donations(&mut self, id: usize) -> u128 {
-        let crowdfund: &mut Crowdfund = self.crowdfunds.get_mut(id).unwrap();
-        return crowdfund.total_donations;
+    fn internal_donations_page(
+        &self,
+        donation_ids: &Vector<u64>,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<DonationView> {
+        let to_index = std::cmp::min(from_index.saturating_add(limit), donation_ids.len());
+        (from_index..to_index)
+            .map(|index| {
+                self.donations
+                    .get(donation_ids.get(index).unwrap())
+                    .unwrap()
+                    .into()
+            })
+            .collect()
     }
 }
 
-// near call crowdfunddapp.verkhohliad.testnet add_crowdfund '{"title": "Eliots eye sight", "donate": 30, "description":"Raise funds for little Eliot to see again. Loss of sight was caused by an accident to the head"}' --accountId verkhohliad.testnet
+// near call crowdfunddapp.verkhohliad.testnet storage_deposit '{}' --deposit 0.1 --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet add_crowdfund '{"title": "Eliots eye sight", "donation_target": "30000000000000000000000000", "description":"Raise funds for little Eliot to see again. Loss of sight was caused by an accident to the head", "duration_days": 30, "milestones": [{"title": "Surgery", "share_bps": 7000, "due_date": 1700000000000000000}, {"title": "Recovery", "share_bps": 3000, "due_date": 1710000000000000000}], "media": "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/cover.png"}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet edit_crowdfund '{"id":0, "description":"Surgery is booked for next month", "donation_target": "40000000000000000000000000"}' --accountId verkhohliad.testnet
 
 // near call crowdfunddapp.verkhohliad.testnet add_vote '{"id":0}' --accountId verkhohliad.testnet
 
-// near call crowdfunddapp.verkhohliad.testnet add_donation '{"id":0, "amount":1}' --accountId verkhohliad.testnet
+// near call crowdfunddapp.verkhohliad.testnet remove_vote '{"id":0}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet add_donation '{"id":0, "memo":"Get well soon", "tier_id":0}' --deposit 1 --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet add_reward_tier '{"id":0, "tier": {"min_pledge": 1000000000000000000000000, "description":"Thank you card", "quantity": 100, "estimated_delivery": 1700000000000000000}}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet withdraw '{"id":0}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet vote_milestone '{"id":0, "approve": true}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet cancel_crowdfund '{"id":0, "reason":"Treatment is covered by insurance"}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet claim_refund '{"id":0}' --accountId verkhohliad.testnet
+
+// near call usdc.fakes.testnet ft_transfer_call '{"receiver_id":"crowdfunddapp.verkhohliad.testnet", "amount":"1000000", "msg":"{\"crowdfund_id\":0}"}' --depositYocto 1 --gas 100000000000000 --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet process_refunds '{"id":0, "limit":10}' --accountId verkhohliad.testnet
 
-// near call crowdfunddapp.verkhohliad.testnet list_crowdfunds --accountId verkhohliad.testnet
+// near view crowdfunddapp.verkhohliad.testnet get_donations_for_crowdfund '{"id":0, "from_index":0, "limit":10}'
+
+// near view crowdfunddapp.verkhohliad.testnet get_donations_by_donor '{"account_id":"verkhohliad.testnet", "from_index":0, "limit":10}'
+
+// near call crowdfunddapp.verkhohliad.testnet set_config '{"config": {"min_donation": "100000000000000000000000", "max_duration_days": 90, "fee_bps": 250}}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet withdraw_treasury '{"amount": "1000000000000000000000000"}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet fund_matching_round '{"round_id":0}' --deposit 100 --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet join_matching_round '{"round_id":0, "id":0}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet settle_match '{"round_id":0, "id":0}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet reclaim_matching_funds '{"round_id":0}' --accountId verkhohliad.testnet
+
+// near view crowdfunddapp.verkhohliad.testnet nft_tokens_for_owner '{"account_id":"verkhohliad.testnet"}'
+
+// near view crowdfunddapp.verkhohliad.testnet list_crowdfunds '{"from_index":0, "limit":10}'
+
+// near view crowdfunddapp.verkhohliad.testnet list_crowdfunds_sorted '{"sort_by":"TotalDonations", "from_index":0, "limit":10}'
+
+// near view crowdfunddapp.verkhohliad.testnet get_crowdfund '{"id":0}'
+
+// near view crowdfunddapp.verkhohliad.testnet get_contract_stats
+
+// near call crowdfunddapp.verkhohliad.testnet create_pledge '{"id":0, "instalment":"5000000000000000000000000", "interval_days":30}' --deposit 60 --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet process_due_pledges '{"limit":20}' --accountId verkhohliad.testnet
+
+// near call crowdfunddapp.verkhohliad.testnet upgrade --base64 "$(base64 -w0 out/main.wasm)" --gas 300000000000000 --accountId verkhohliad.testnet
 
 /*
  * The rest of this file holds the inline tests for the code above
@@ -113,8 +975,47 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::ONE_NEAR;
+    use near_sdk::json_types::ValidAccountId;
+    use near_sdk::serde_json;
+    use near_sdk::test_utils::get_logs;
     use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, VMContext};
+    use near_sdk::PromiseOrValue;
+    use near_sdk::VMContext;
+    use std::convert::TryFrom;
+
+    // near_sdk's testing_env! keeps the mocked storage but takes the storage usage from the
+    // new context, so state written under an earlier context breaks the accounting
+    macro_rules! testing_env {
+        ($context:expr) => {
+            set_context($context)
+        };
+    }
+
+    /// Switches to a new context, keeping the mocked storage and how much of it is used
+    fn set_context(mut context: VMContext) {
+        let storage = match env::take_blockchain_interface() {
+            Some(blockchain) => {
+                env::set_blockchain_interface(blockchain);
+                context.storage_usage = env::storage_usage();
+                let mut blockchain = env::take_blockchain_interface().unwrap();
+                blockchain
+                    .as_mut_mocked_blockchain()
+                    .unwrap()
+                    .take_storage()
+            }
+            None => Default::default(),
+        };
+        env::set_blockchain_interface(Box::new(MockedBlockchain::new(
+            context,
+            Default::default(),
+            Default::default(),
+            vec![],
+            storage,
+            Default::default(),
+            None,
+        )));
+    }
 
     // mock the context for testing, notice "signer_account_id" that was accessed above from env::
     fn get_context(input: Vec<u8>, is_view: bool) -> VMContext {
@@ -126,7 +1027,7 @@ mod tests {
             input,
             block_index: 0,
             block_timestamp: 0,
-            account_balance: 0,
+            account_balance: ONE_NEAR * 1_000,
             account_locked_balance: 0,
             storage_usage: 0,
             attached_deposit: 0,
@@ -138,27 +1039,1527 @@ mod tests {
         }
     }
 
+    /// Registers the accounts used by the tests with enough storage deposit for all of them
+    fn new_contract(owner: &str) -> Contract {
+        let mut contract = Contract::init(owner.to_string());
+        for account_id in &[
+            "carol_near",
+            "dave_near",
+            "erin_near",
+            "frank_near",
+            "grace_near",
+        ] {
+            contract
+                .storage_accounts
+                .insert(&account_id.to_string(), &StorageAccount::new(ONE_NEAR));
+        }
+        contract
+    }
+
+    fn add_sample_crowdfund(contract: &mut Contract) {
+        contract.add_crowdfund(
+            "Eliots eye sight".to_string(),
+            U128(toYocto(30u128)),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn donation_is_held_in_escrow() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 2;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+
+        assert_eq!(ONE_NEAR * 2, contract.get_total_donations(0).0);
+        assert_eq!(ONE_NEAR * 2, contract.donations.get(0).unwrap().amount);
+    }
+
     #[test]
-    fn set_then_get_greeting() {
+    #[should_panic(expected = "ERR_DEPOSIT_TOO_SMALL")]
+    fn donation_without_deposit() {
         let context = get_context(vec![], false);
         testing_env!(context);
-        let mut contract = Welcome::default();
-        contract.set_greeting("howdy".to_string());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+        contract.add_donation(0, None, None);
+    }
+
+    #[test]
+    fn creator_withdraws_after_target() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 30;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        assert_eq!(CrowdfundStatus::Succeeded, contract.get_crowdfund_status(0));
+        contract.withdraw(0);
+        assert_eq!(CrowdfundStatus::Withdrawn, contract.get_crowdfund_status(0));
+        assert_eq!(ONE_NEAR * 30, contract.crowdfunds.get(0).unwrap().withdrawn);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TARGET_NOT_REACHED")]
+    fn withdraw_before_target() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        assert_eq!(CrowdfundStatus::Failed, contract.get_crowdfund_status(0));
+        contract.withdraw(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CREATOR")]
+    fn withdraw_by_non_creator() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context);
+        contract.withdraw(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CAMPAIGN_CLOSED")]
+    fn donation_after_deadline() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+    }
+
+    #[test]
+    fn donor_claims_refund_from_failed_campaign() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        contract.add_donation(0, None, None);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        contract.claim_refund(0, None);
+        assert_eq!(ONE_NEAR * 2, contract.crowdfunds.get(0).unwrap().refunded);
+        assert!(contract.donations.iter().all(|donation| donation.refunded));
+    }
+
+    #[test]
+    fn process_refunds_in_batches() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        for donor in &["dave_near", "erin_near", "frank_near"] {
+            context.predecessor_account_id = donor.to_string();
+            testing_env!(context.clone());
+            contract.add_donation(0, None, None);
+        }
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        assert_eq!(2, contract.process_refunds(0, 2));
+        assert_eq!(1, contract.process_refunds(0, 2));
+        assert_eq!(0, contract.process_refunds(0, 2));
+        assert_eq!(ONE_NEAR * 3, contract.crowdfunds.get(0).unwrap().refunded);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_REFUNDABLE")]
+    fn refund_from_active_campaign() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+        contract.claim_refund(0, None);
+    }
+
+    #[test]
+    fn donation_history_by_crowdfund_and_donor() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context.clone());
+        contract.add_donation(0, Some("Get well soon".to_string()), None);
+        contract.add_donation(1, None, None);
+
+        context.predecessor_account_id = "erin_near".to_string();
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+
+        let backers = contract.get_donations_for_crowdfund(0, 0, 10);
+        assert_eq!(2, backers.len());
+        assert_eq!("dave_near", backers[0].donor);
+        assert_eq!(Some("Get well soon".to_string()), backers[0].memo);
+        assert_eq!(1, contract.get_donations_for_crowdfund(0, 1, 10).len());
+
+        let history = contract.get_donations_by_donor("dave_near".to_string(), 0, 10);
         assert_eq!(
-            "howdy".to_string(),
-            contract.get_greeting("bob_near".to_string())
+            vec![0, 1],
+            history.iter().map(|d| d.crowdfund_id).collect::<Vec<_>>()
         );
     }
 
     #[test]
-    fn get_default_greeting() {
-        let context = get_context(vec![], true);
+    fn paginated_and_filtered_listings() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context.clone());
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 15;
         testing_env!(context);
-        let contract = Welcome::default();
-        // this test did not call set_greeting so should return the default "Hello" greeting
+        contract.add_donation(1, None, None);
+
+        assert_eq!(2, contract.list_crowdfunds(0, 2).len());
+        assert_eq!(1, contract.list_crowdfunds(2, 2).len());
+        assert_eq!(0, contract.list_crowdfunds(5, 2).len());
+
+        let by_creator = contract.list_crowdfunds_by_creator("dave_near".to_string(), 0, 10);
+        assert_eq!(vec![2], by_creator.iter().map(|c| c.id).collect::<Vec<_>>());
+
+        let active = contract.list_crowdfunds_by_status(CrowdfundStatus::Active, 1, 10);
+        assert_eq!(2, active.len());
+
+        let half_funded = contract.list_crowdfunds_by_progress(50, 0, 10);
         assert_eq!(
-            "Hello".to_string(),
-            contract.get_greeting("francis.near".to_string())
+            vec![1],
+            half_funded.iter().map(|c| c.id).collect::<Vec<_>>()
         );
+
+        let sorted = contract.list_crowdfunds_sorted(CrowdfundSort::TotalDonations, 0, 3);
+        assert_eq!(1, sorted[0].id);
+        let newest = contract.list_crowdfunds_sorted(CrowdfundSort::CreatedAt, 0, 2);
+        assert_eq!(vec![2, 1], newest.iter().map(|c| c.id).collect::<Vec<_>>());
+        let window = contract.list_crowdfunds_by_status(CrowdfundStatus::Active, 0, 1);
+        assert_eq!(vec![0], window.iter().map(|c| c.id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn one_vote_per_account() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        contract.add_vote(0);
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context);
+        contract.add_vote(0);
+        assert!(contract.has_voted(0, "carol_near".to_string()));
+        assert_eq!(2, contract.list_crowdfunds(0, 1)[0].total_votes);
+
+        contract.remove_vote(0);
+        assert!(!contract.has_voted(0, "dave_near".to_string()));
+        assert_eq!(1, contract.list_crowdfunds(0, 1)[0].total_votes);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_VOTED")]
+    fn duplicate_vote() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+        contract.add_vote(0);
+        contract.add_vote(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CROWDFUND_NOT_FOUND: Crowdfund does not exist")]
+    fn vote_for_unknown_crowdfund() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("alice_near");
+        contract.add_vote(7);
+    }
+
+    #[test]
+    fn two_step_ownership_transfer() {
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = "alice_near".to_string();
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        contract.propose_owner("dave_near".to_string());
+        assert_eq!("alice_near", contract.get_owner());
+
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context);
+        contract.accept_ownership();
+        assert_eq!("dave_near", contract.get_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn paused_contract_rejects_donations() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        contract.pause();
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn pause_by_non_owner() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("alice_near");
+        contract.pause();
+    }
+
+    #[test]
+    fn delisted_crowdfund_is_hidden() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+        contract.set_crowdfund_delisted(0, true);
+
+        let listed = contract.list_crowdfunds(0, 10);
+        assert_eq!(vec![1], listed.iter().map(|c| c.id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_TOO_SMALL")]
+    fn donation_below_configured_minimum() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.set_config(PlatformConfigView {
+            min_donation: U128(ONE_NEAR),
+            max_duration_days: 90,
+            fee_bps: 0,
+        });
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR / 2;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+    }
+
+    #[test]
+    fn donation_emits_nep297_event() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+
+        let logs = get_logs();
+        assert_eq!(
+            r#"EVENT_JSON:{"standard":"crowdfund","version":"1.0.0","event":"donation_received","data":{"crowdfund_id":0,"donation_id":0,"donor":"carol_near","amount":"1000000000000000000000000"}}"#,
+            logs.last().unwrap().as_str()
+        );
+    }
+
+    fn usdc_target(target: u128) -> TokenTarget {
+        TokenTarget {
+            token_id: "usdc_near".to_string(),
+            target: U128(target),
+        }
+    }
+
+    #[test]
+    fn token_donation_via_ft_on_transfer() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.add_accepted_token("usdc_near".to_string());
+        add_sample_crowdfund(&mut contract);
+        contract.set_crowdfund_tokens(0, vec![usdc_target(1_000)]);
+
+        context.predecessor_account_id = "usdc_near".to_string();
+        testing_env!(context);
+        let result = contract.ft_on_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            U128(500),
+            r#"{"crowdfund_id": 0}"#.to_string(),
+        );
+        match result {
+            PromiseOrValue::Value(unused) => assert_eq!(0, unused.0),
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+        let escrows = contract.get_crowdfund_tokens(0);
+        assert_eq!(500, escrows[0].total_donations.0);
+        assert_eq!(0, contract.get_total_donations(0).0);
+        let donation = contract.donations.get(0).unwrap();
+        assert_eq!("dave_near", donation.donor);
+        assert_eq!(Some("usdc_near".to_string()), donation.token_id);
+    }
+
+    #[test]
+    fn token_only_crowdfund_succeeds_and_withdraws() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.add_accepted_token("usdc_near".to_string());
+        add_sample_crowdfund(&mut contract);
+        contract.set_crowdfund_tokens(0, vec![usdc_target(1_000)]);
+
+        context.predecessor_account_id = "usdc_near".to_string();
+        testing_env!(context.clone());
+        contract.ft_on_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            U128(600),
+            r#"{"crowdfund_id": 0}"#.to_string(),
+        );
+        contract.ft_on_transfer(
+            ValidAccountId::try_from("erin_near").unwrap(),
+            U128(400),
+            r#"{"crowdfund_id": 0}"#.to_string(),
+        );
+        assert_eq!(100, contract.get_progress(0));
+
+        context.predecessor_account_id = "carol_near".to_string();
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        assert_eq!(CrowdfundStatus::Succeeded, contract.get_crowdfund_status(0));
+        contract.withdraw_token(0, "usdc_near".to_string());
+        assert_eq!(1_000, contract.get_crowdfund_tokens(0)[0].withdrawn.0);
+    }
+
+    #[test]
+    fn token_refunds_are_sized_to_the_attached_gas() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.add_accepted_token("usdc_near".to_string());
+        add_sample_crowdfund(&mut contract);
+        contract.set_crowdfund_tokens(0, vec![usdc_target(1_000)]);
+
+        context.predecessor_account_id = "usdc_near".to_string();
+        testing_env!(context.clone());
+        for donor in &["dave_near", "erin_near", "frank_near"] {
+            contract.ft_on_transfer(
+                ValidAccountId::try_from(*donor).unwrap(),
+                U128(100),
+                r#"{"crowdfund_id": 0}"#.to_string(),
+            );
+        }
+
+        context.predecessor_account_id = "grace_near".to_string();
+        context.block_timestamp = ONE_DAY * 30;
+        context.prepaid_gas = 100_000_000_000_000;
+        testing_env!(context.clone());
+        assert_eq!(2, contract.process_refunds(0, REFUND_BATCH_LIMIT));
+        testing_env!(context);
+        assert_eq!(1, contract.process_refunds(0, REFUND_BATCH_LIMIT));
+        assert_eq!(300, contract.get_crowdfund_tokens(0)[0].refunded.0);
+    }
+
+    #[test]
+    fn token_not_accepted_by_crowdfund_is_returned() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.add_accepted_token("usdc_near".to_string());
+        add_sample_crowdfund(&mut contract);
+
+        context.predecessor_account_id = "usdc_near".to_string();
+        testing_env!(context);
+        let result = contract.ft_on_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            U128(500),
+            r#"{"crowdfund_id": 0}"#.to_string(),
+        );
+        match result {
+            PromiseOrValue::Value(unused) => assert_eq!(500, unused.0),
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+        assert_eq!(0, contract.donations.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOKEN_TARGET_FIXED")]
+    fn token_target_is_fixed_once_donated() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.add_accepted_token("usdc_near".to_string());
+        add_sample_crowdfund(&mut contract);
+        contract.set_crowdfund_tokens(0, vec![usdc_target(1_000)]);
+
+        context.predecessor_account_id = "usdc_near".to_string();
+        testing_env!(context.clone());
+        contract.ft_on_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            U128(500),
+            r#"{"crowdfund_id": 0}"#.to_string(),
+        );
+
+        context.predecessor_account_id = "carol_near".to_string();
+        testing_env!(context);
+        contract.set_crowdfund_tokens(0, vec![usdc_target(500)]);
+    }
+
+    #[test]
+    fn platform_fee_is_kept_on_withdrawal() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.set_config(PlatformConfigView {
+            fee_bps: 250,
+            ..PlatformConfig::default().into()
+        });
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 40;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+
+        let projected = contract.get_crowdfund_payout(0);
+        assert_eq!(ONE_NEAR * 40, projected.gross_raised.0);
+        assert_eq!(ONE_NEAR, projected.fees.0);
+        assert_eq!(ONE_NEAR * 39, projected.net_payout.0);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        contract.withdraw(0);
+        assert_eq!(ONE_NEAR, contract.crowdfunds.get(0).unwrap().fees);
+        assert_eq!(ONE_NEAR, contract.get_crowdfund_payout(0).fees.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_FEE")]
+    fn fee_above_one_hundred_percent() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.set_config(PlatformConfigView {
+            fee_bps: 10_001,
+            ..PlatformConfig::default().into()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_DURATION")]
+    fn max_duration_above_cap() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.set_config(PlatformConfigView {
+            max_duration_days: MAX_DURATION_DAYS + 1,
+            ..PlatformConfig::default().into()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_TREASURY")]
+    fn treasury_withdrawal_above_balance() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.withdraw_treasury(U128(ONE_NEAR));
+    }
+
+    fn sample_reward_tier(quantity: Option<u64>) -> RewardTierInput {
+        RewardTierInput {
+            min_pledge: U128(ONE_NEAR * 5),
+            description: "Thank you card".to_string(),
+            quantity,
+            estimated_delivery: U64(ONE_DAY * 60),
+        }
+    }
+
+    #[test]
+    fn donors_claim_reward_tiers() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        assert_eq!(0, contract.add_reward_tier(0, sample_reward_tier(Some(2))));
+
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = ONE_NEAR * 5;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, Some(0));
+        contract.add_donation(0, None, None);
+        context.predecessor_account_id = "erin_near".to_string();
+        testing_env!(context);
+        contract.add_donation(0, None, Some(0));
+
+        assert_eq!(2, contract.get_reward_tiers(0)[0].claimed);
+        let backers = contract.get_reward_tier_backers(0, 0, 0, 10);
+        assert_eq!(2, backers.len());
+        assert_eq!("dave_near", backers[0].donor);
+        let backers = contract.get_reward_tier_backers(0, 0, 1, 10);
+        assert_eq!(1, backers.len());
+        assert_eq!("erin_near", backers[0].donor);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_TIER_SOLD_OUT")]
+    fn reward_tier_sold_out() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        contract.add_reward_tier(0, sample_reward_tier(Some(1)));
+
+        context.attached_deposit = ONE_NEAR * 5;
+        testing_env!(context);
+        contract.add_donation(0, None, Some(0));
+        contract.add_donation(0, None, Some(0));
+    }
+
+    #[test]
+    fn donation_mints_transferable_backer_badge() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        let carol = ValidAccountId::try_from("carol_near").unwrap();
+        let badges = contract.nft_tokens_for_owner(carol.clone(), None, None);
+        assert_eq!(1, badges.len());
+        assert_eq!("0", badges[0].token_id);
+        assert!(badges[0]
+            .metadata
+            .as_ref()
+            .unwrap()
+            .extra
+            .as_ref()
+            .unwrap()
+            .contains(r#""crowdfund_id":0"#));
+
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            "0".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(0, contract.nft_supply_for_owner(carol).0);
+        assert_eq!(
+            "dave_near",
+            contract.nft_token("0".to_string()).unwrap().owner_id
+        );
+        assert_eq!(1, contract.nft_total_supply().0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn paused_contract_rejects_badge_transfers() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.pause();
+
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            "0".to_string(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn matching_round_favours_broad_support() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+        assert_eq!(0, contract.create_matching_round(10, 10_000));
+        contract.join_matching_round(0, 0);
+        contract.join_matching_round(0, 1);
+
+        context.attached_deposit = ONE_NEAR * 100;
+        testing_env!(context.clone());
+        contract.fund_matching_round(0);
+
+        // four backers of 1 NEAR for crowdfund 0, one backer of 4 NEAR for crowdfund 1
+        context.attached_deposit = ONE_NEAR;
+        for donor in &["dave_near", "erin_near", "frank_near", "grace_near"] {
+            context.predecessor_account_id = donor.to_string();
+            testing_env!(context.clone());
+            contract.add_donation(0, None, None);
+        }
+        context.attached_deposit = ONE_NEAR * 4;
+        testing_env!(context.clone());
+        contract.add_donation(1, None, None);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 10;
+        testing_env!(context);
+        contract.finalize_matching_round(0);
+
+        let round = contract.get_matching_round(0);
+        assert!(round.finalized);
+        assert_eq!(ONE_NEAR * 100, round.entries[0].matched.0);
+        assert_eq!(0, round.entries[1].matched.0);
+        assert_eq!(
+            ONE_NEAR * 4,
+            contract.crowdfunds.get(0).unwrap().total_donations
+        );
+    }
+
+    #[test]
+    fn match_is_credited_only_to_successful_crowdfunds() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+        contract.create_matching_round(10, 5_000);
+        contract.join_matching_round(0, 0);
+        contract.join_matching_round(0, 1);
+
+        context.attached_deposit = ONE_NEAR * 100;
+        testing_env!(context.clone());
+        contract.fund_matching_round(0);
+
+        // crowdfund 0 reaches its target, crowdfund 1 does not
+        for donor in &["dave_near", "erin_near", "frank_near"] {
+            context.predecessor_account_id = donor.to_string();
+            context.attached_deposit = ONE_NEAR * 10;
+            testing_env!(context.clone());
+            contract.add_donation(0, None, None);
+            context.attached_deposit = ONE_NEAR;
+            testing_env!(context.clone());
+            contract.add_donation(1, None, None);
+        }
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 10;
+        testing_env!(context.clone());
+        contract.finalize_matching_round(0);
+        let round = contract.get_matching_round(0);
+        assert_eq!(ONE_NEAR * 50, round.entries[0].matched.0);
+        assert!(round.entries[1].matched.0 > 0);
+        assert_eq!(
+            ONE_NEAR * 30,
+            contract.crowdfunds.get(0).unwrap().total_donations
+        );
+
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context.clone());
+        contract.settle_match(0, 0);
+        contract.settle_match(0, 1);
+        let crowdfund = contract.crowdfunds.get(0).unwrap();
+        assert_eq!(ONE_NEAR * 80, crowdfund.total_donations);
+        assert_eq!(ONE_NEAR * 50, crowdfund.matched);
+        assert_eq!(0, contract.crowdfunds.get(1).unwrap().matched);
+        assert!(contract.get_matching_round(0).entries[1].settled);
+
+        // the sponsor gets back both the capped remainder and the returned match
+        context.predecessor_account_id = "carol_near".to_string();
+        testing_env!(context);
+        assert_eq!(ONE_NEAR * 50, contract.get_matching_round(0).unallocated.0);
+        contract.reclaim_matching_funds(0);
+        let key = (0, "carol_near".to_string());
+        assert_eq!(
+            ONE_NEAR * 50,
+            contract.round_sponsors.get(&key).unwrap().reclaimed
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_TO_RECLAIM")]
+    fn reclaim_matching_funds_without_deposit() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.create_matching_round(10, 10_000);
+        context.block_timestamp = ONE_DAY * 10;
+        testing_env!(context.clone());
+        contract.finalize_matching_round(0);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context);
+        contract.reclaim_matching_funds(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MATCH_PENDING")]
+    fn match_is_held_while_crowdfund_is_active() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        contract.create_matching_round(10, 10_000);
+        contract.join_matching_round(0, 0);
+
+        context.attached_deposit = ONE_NEAR * 100;
+        testing_env!(context.clone());
+        contract.fund_matching_round(0);
+        context.attached_deposit = ONE_NEAR;
+        for donor in &["dave_near", "erin_near"] {
+            context.predecessor_account_id = donor.to_string();
+            testing_env!(context.clone());
+            contract.add_donation(0, None, None);
+        }
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 10;
+        testing_env!(context);
+        contract.finalize_matching_round(0);
+        contract.settle_match(0, 0);
+    }
+
+    #[test]
+    fn storage_deposit_pays_for_crowdfunds() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = ONE_NEAR;
+        testing_env!(context.clone());
+        let mut contract = Contract::init("alice_near".to_string());
+        contract.storage_deposit(None, None);
+
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        add_sample_crowdfund(&mut contract);
+        let balance = contract
+            .storage_balance_of(ValidAccountId::try_from("carol_near").unwrap())
+            .unwrap();
+        assert_eq!(ONE_NEAR, balance.total.0);
+        assert!(balance.available.0 < ONE_NEAR);
+
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.storage_withdraw(None);
+        let balance = contract
+            .storage_balance_of(ValidAccountId::try_from("carol_near").unwrap())
+            .unwrap();
+        assert_eq!(0, balance.available.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_NOT_REGISTERED")]
+    fn crowdfund_without_storage_deposit() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::init("alice_near".to_string());
+        add_sample_crowdfund(&mut contract);
+    }
+
+    #[test]
+    fn edits_keep_earlier_versions() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        contract.edit_crowdfund(
+            0,
+            Some("Eliots eye surgery".to_string()),
+            None,
+            Some(U128(toYocto(40u128))),
+            None,
+        );
+
+        let crowdfund = contract.crowdfunds.get(0).unwrap();
+        assert_eq!(1, crowdfund.version);
+        assert_eq!("Eliots eye surgery", crowdfund.title);
+        assert_eq!(ONE_NEAR * 40, crowdfund.donation_target);
+        let revisions = contract.get_crowdfund_revisions(0, 0, 10);
+        assert_eq!(1, revisions.len());
+        assert_eq!("Eliots eye sight", revisions[0].title);
+        assert_eq!(ONE_NEAR * 30, revisions[0].donation_target.0);
+    }
+
+    #[test]
+    fn edit_replaces_media() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        contract.edit_crowdfund(
+            0,
+            None,
+            None,
+            None,
+            Some("https://example.com/cover.png".to_string()),
+        );
+
+        let crowdfund = contract.crowdfunds.get(0).unwrap();
+        assert_eq!(
+            Some("https://example.com/cover.png".to_string()),
+            crowdfund.media
+        );
+        assert_eq!(None, contract.get_crowdfund_revisions(0, 0, 10)[0].media);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TARGET")]
+    fn target_below_amount_raised() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 20;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = 0;
+        testing_env!(context);
+        contract.edit_crowdfund(0, None, None, Some(U128(toYocto(10u128))), None);
+    }
+
+    #[test]
+    fn cancelled_crowdfund_refunds_donors() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 5;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = 0;
+        testing_env!(context);
+        contract.cancel_crowdfund(0, "Treatment is covered by insurance".to_string());
+        assert_eq!(CrowdfundStatus::Cancelled, contract.get_crowdfund_status(0));
+
+        contract.claim_refund(0, None);
+        assert_eq!(ONE_NEAR * 5, contract.crowdfunds.get(0).unwrap().refunded);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_CANCEL_REASON")]
+    fn cancel_with_overlong_reason() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+        contract.cancel_crowdfund(0, "x".repeat(MAX_CANCEL_REASON_LENGTH + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CREATOR")]
+    fn cancel_by_stranger() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("alice_near");
+        add_sample_crowdfund(&mut contract);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context);
+        contract.cancel_crowdfund(0, "Spam".to_string());
+    }
+
+    fn add_milestone_crowdfund(contract: &mut Contract) {
+        contract.add_crowdfund(
+            "Eliots eye sight".to_string(),
+            U128(toYocto(30u128)),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            Some(vec![
+                MilestoneInput {
+                    title: "Surgery".to_string(),
+                    share_bps: 6_000,
+                    due_date: U64(ONE_DAY * 40),
+                },
+                MilestoneInput {
+                    title: "Recovery".to_string(),
+                    share_bps: 4_000,
+                    due_date: U64(ONE_DAY * 50),
+                },
+            ]),
+            None,
+        );
+    }
+
+    #[test]
+    fn milestones_release_funds_and_rejection_refunds_the_rest() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_milestone_crowdfund(&mut contract);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = ONE_NEAR * 40;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 40;
+        testing_env!(context.clone());
+        contract.start_milestone_vote(0);
+        contract.vote_milestone(0, true);
+
+        context.predecessor_account_id = "carol_near".to_string();
+        context.block_timestamp = ONE_DAY * 47;
+        testing_env!(context.clone());
+        contract.finalize_milestone(0);
+        contract.withdraw(0);
+        assert_eq!(ONE_NEAR * 24, contract.crowdfunds.get(0).unwrap().withdrawn);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        context.block_timestamp = ONE_DAY * 50;
+        testing_env!(context.clone());
+        contract.start_milestone_vote(0);
+        contract.vote_milestone(0, false);
+        context.block_timestamp = ONE_DAY * 57;
+        testing_env!(context);
+        contract.finalize_milestone(0);
+        assert_eq!(CrowdfundStatus::Failed, contract.get_crowdfund_status(0));
+
+        contract.claim_refund(0, None);
+        assert_eq!(ONE_NEAR * 16, contract.crowdfunds.get(0).unwrap().refunded);
+    }
+
+    #[test]
+    fn milestone_without_quorum_is_rejected() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_milestone_crowdfund(&mut contract);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = ONE_NEAR * 36;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.predecessor_account_id = "erin_near".to_string();
+        context.attached_deposit = ONE_NEAR * 4;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 40;
+        testing_env!(context.clone());
+        contract.start_milestone_vote(0);
+        contract.vote_milestone(0, true);
+        context.block_timestamp = ONE_DAY * 47;
+        testing_env!(context);
+        contract.finalize_milestone(0);
+        assert_eq!(
+            MilestoneStatus::Rejected,
+            contract.get_milestones(0)[0].status
+        );
+        assert_eq!(CrowdfundStatus::Failed, contract.get_crowdfund_status(0));
+    }
+
+    #[test]
+    fn token_backers_vote_on_milestones_and_get_their_tokens_back() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        contract.add_accepted_token("usdc_near".to_string());
+        add_milestone_crowdfund(&mut contract);
+        contract.set_crowdfund_tokens(0, vec![usdc_target(1_000)]);
+
+        context.predecessor_account_id = "usdc_near".to_string();
+        testing_env!(context.clone());
+        contract.ft_on_transfer(
+            ValidAccountId::try_from("dave_near").unwrap(),
+            U128(1_000),
+            r#"{"crowdfund_id": 0}"#.to_string(),
+        );
+
+        context.predecessor_account_id = "dave_near".to_string();
+        context.block_timestamp = ONE_DAY * 40;
+        testing_env!(context.clone());
+        contract.start_milestone_vote(0);
+        contract.vote_milestone(0, false);
+        assert_eq!(
+            toYocto(30u128),
+            contract.get_milestones(0)[0].rejection_weight.0
+        );
+
+        context.block_timestamp = ONE_DAY * 47;
+        testing_env!(context);
+        contract.finalize_milestone(0);
+        assert_eq!(CrowdfundStatus::Failed, contract.get_crowdfund_status(0));
+        contract.claim_refund(0, Some("usdc_near".to_string()));
+        assert_eq!(1_000, contract.get_crowdfund_tokens(0)[0].refunded.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MILESTONE_NOT_DUE")]
+    fn creator_cannot_start_vote_early() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_milestone_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 40;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        contract.start_milestone_vote(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_TO_WITHDRAW")]
+    fn withdraw_before_milestone_approval() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_milestone_crowdfund(&mut contract);
+
+        context.attached_deposit = ONE_NEAR * 40;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context);
+        contract.withdraw(0);
+    }
+
+    fn legacy_state() -> migration::LegacyContract {
+        migration::LegacyContract {
+            owner: "carol_near".to_string(),
+            crowdfunds: vec![migration::LegacyCrowdfund {
+                id: 0,
+                creator: "dave_near".to_string(),
+                created_at: 42,
+                title: "Eliots eye sight".to_string(),
+                donation_target: 30,
+                total_donations: ONE_NEAR * 2,
+                total_votes: 1,
+                description: "Raise funds for little Eliot to see again".to_string(),
+                votes: vec!["erin_near".to_string()],
+            }],
+            donations: vec![migration::LegacyDonation {
+                amount: ONE_NEAR * 2,
+                donor: "erin_near".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn migrate_from_legacy_state() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        env::storage_write(b"STATE", &legacy_state().try_to_vec().unwrap());
+        let contract = Contract::migrate();
+
+        assert_eq!(STATE_VERSION, contract.get_state_version());
+        assert_eq!(1, contract.crowdfund_count());
+        let crowdfund = contract.crowdfunds.get(0).unwrap();
+        assert_eq!("dave_near", crowdfund.creator);
+        assert_eq!(42, crowdfund.created_at);
+        assert_eq!(toYocto(30u128), crowdfund.donation_target);
+        assert_eq!(0, crowdfund.total_donations);
+        assert!(contract
+            .votes_by_crowdfund
+            .get(&0)
+            .unwrap()
+            .contains(&"erin_near".to_string()));
+        let donation = contract.donations.get(0).unwrap();
+        assert_eq!(migration::LEGACY_CROWDFUND_ID, donation.crowdfund_id);
+        assert!(donation.refunded);
+        assert!(contract
+            .donations_by_donor
+            .get(&"erin_near".to_string())
+            .is_some());
+        assert!(get_logs()[0].contains("state_migrated"));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn migrate_by_stranger() {
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = "dave_near".to_string();
+        testing_env!(context);
+        env::storage_write(b"STATE", &legacy_state().try_to_vec().unwrap());
+        Contract::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNKNOWN_STATE_VERSION")]
+    fn migrate_from_unknown_state_version() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.state_version = STATE_VERSION + 1;
+        env::storage_write(b"STATE", &contract.try_to_vec().unwrap());
+        Contract::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_GAS")]
+    fn upgrade_without_gas_for_migrate() {
+        let mut context = get_context(vec![0, 97, 115, 109], false);
+        context.prepaid_gas = UPGRADE_GAS;
+        testing_env!(context);
+        let contract = new_contract("carol_near");
+        contract.upgrade();
+    }
+
+    #[test]
+    fn add_crowdfund_with_media() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        let cid = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+        contract.add_crowdfund(
+            "Eliots eye sight".to_string(),
+            U128(toYocto(30u128)),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            None,
+            Some(format!("ipfs://{}/cover.png", cid)),
+        );
+        contract.add_crowdfund(
+            "Eliots eye sight".to_string(),
+            U128(toYocto(30u128)),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            None,
+            Some("https://example.com/eliot.png".to_string()),
+        );
+        assert!(contract.crowdfunds.get(0).unwrap().media.is_some());
+        assert_eq!(
+            Some("https://example.com/eliot.png".to_string()),
+            contract.crowdfunds.get(1).unwrap().media
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TITLE")]
+    fn add_crowdfund_with_blank_title() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.add_crowdfund(
+            "   ".to_string(),
+            U128(toYocto(30u128)),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TARGET")]
+    fn add_crowdfund_with_zero_target() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.add_crowdfund(
+            "Eliots eye sight".to_string(),
+            U128(0),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_MEDIA")]
+    fn add_crowdfund_with_bad_media() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = new_contract("carol_near");
+        contract.add_crowdfund(
+            "Eliots eye sight".to_string(),
+            U128(toYocto(30u128)),
+            "Raise funds for little Eliot to see again".to_string(),
+            30,
+            None,
+            Some("javascript:alert(1)".to_string()),
+        );
+    }
+
+    #[test]
+    fn views_serialize_amounts_as_strings() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        context.attached_deposit = ONE_NEAR * 2;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+
+        let summary = serde_json::to_value(&contract.list_crowdfunds(0, 1)[0]).unwrap();
+        assert_eq!("30000000000000000000000000", summary["donation_target"]);
+        assert_eq!("2000000000000000000000000", summary["total_donations"]);
+        assert_eq!("0", summary["created_at"]);
+        let donation =
+            serde_json::to_value(&contract.get_donations_for_crowdfund(0, 0, 1)[0]).unwrap();
+        assert_eq!("2000000000000000000000000", donation["amount"]);
+    }
+
+    #[test]
+    fn read_only_crowdfund_views() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+        contract.add_vote(0);
+        context.attached_deposit = ONE_NEAR * 15;
+        testing_env!(context);
+        contract.add_donation(0, None, None);
+
+        let crowdfund = contract.get_crowdfund(0);
+        assert_eq!(
+            "Raise funds for little Eliot to see again",
+            crowdfund.description
+        );
+        assert_eq!(1, crowdfund.total_votes);
+        assert_eq!(ONE_NEAR * 15, crowdfund.total_donations.0);
+        assert_eq!(
+            CrowdfundStatus::Active,
+            contract.get_crowdfund_summary(1).status
+        );
+        assert_eq!(50, contract.get_progress(0));
+        assert_eq!(0, contract.get_progress(1));
+
+        let stats = contract.get_contract_stats();
+        assert_eq!(2, stats.crowdfunds);
+        assert_eq!(2, stats.active_crowdfunds);
+        assert_eq!(1, stats.donations);
+        assert_eq!(ONE_NEAR * 15, stats.total_raised.0);
+    }
+
+    #[test]
+    fn contract_stats_follow_status_changes() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = ONE_NEAR * 30;
+        testing_env!(context.clone());
+        contract.add_donation(0, None, None);
+        context.attached_deposit = ONE_NEAR * 2;
+        testing_env!(context.clone());
+        contract.add_donation(1, None, None);
+
+        context.attached_deposit = 0;
+        context.block_timestamp = ONE_DAY * 30;
+        testing_env!(context.clone());
+        contract.claim_refund(1, None);
+        context.predecessor_account_id = "carol_near".to_string();
+        testing_env!(context);
+        contract.withdraw(0);
+
+        let stats = contract.get_contract_stats();
+        assert_eq!(2, stats.crowdfunds);
+        assert_eq!(0, stats.active_crowdfunds);
+        assert_eq!(1, stats.successful_crowdfunds);
+        assert_eq!(ONE_NEAR * 32, stats.total_raised.0);
+        assert_eq!(ONE_NEAR * 30, stats.total_withdrawn.0);
+        assert_eq!(ONE_NEAR * 2, stats.total_refunded.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CROWDFUND_NOT_FOUND")]
+    fn get_missing_crowdfund() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let contract = new_contract("carol_near");
+        contract.get_crowdfund(0);
+    }
+
+    fn add_sample_pledge(contract: &mut Contract, context: &mut VMContext) {
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = ONE_NEAR * 10;
+        testing_env!(context.clone());
+        contract.create_pledge(0, U128(ONE_NEAR * 4), 7);
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+    }
+
+    #[test]
+    fn pledge_releases_instalments_when_due() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_pledge(&mut contract, &mut context);
+
+        assert_eq!(1, contract.process_due_pledges(10));
+        assert_eq!(0, contract.process_due_pledges(10));
+        context.block_timestamp = ONE_DAY * 7;
+        testing_env!(context.clone());
+        assert_eq!(1, contract.process_due_pledges(10));
+        context.block_timestamp = ONE_DAY * 14;
+        testing_env!(context);
+        assert_eq!(1, contract.process_due_pledges(10));
+
+        let pledge = contract.get_pledge(0);
+        assert!(!pledge.active);
+        assert_eq!(0, pledge.balance.0);
+        assert_eq!(ONE_NEAR * 10, pledge.released.0);
+        assert_eq!(ONE_NEAR * 10, contract.get_total_donations(0).0);
+        assert_eq!(
+            vec![ONE_NEAR * 4, ONE_NEAR * 4, ONE_NEAR * 2],
+            contract
+                .get_donations_by_donor("dave_near".to_string(), 0, 10)
+                .iter()
+                .map(|donation| donation.amount.0)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn pledge_instalment_charges_the_storage_it_adds() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_pledge(&mut contract, &mut context);
+
+        let initial_storage = env::storage_usage();
+        let initial_used = contract
+            .storage_accounts
+            .get(&"dave_near".to_string())
+            .unwrap()
+            .used;
+        contract.process_due_pledges(10);
+        let used = contract
+            .storage_accounts
+            .get(&"dave_near".to_string())
+            .unwrap()
+            .used;
+        assert_eq!(env::storage_usage() - initial_storage, used - initial_used);
+    }
+
+    #[test]
+    fn cancelled_pledge_refunds_the_rest() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_pledge(&mut contract, &mut context);
+        contract.process_due_pledges(10);
+
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.cancel_pledge(0);
+        let pledge = contract.get_pledge(0);
+        assert!(!pledge.active);
+        assert_eq!(ONE_NEAR * 4, pledge.released.0);
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains("pledge_closed") && log.contains("6000000000000000000000000")));
+        assert_eq!(0, contract.process_due_pledges(10));
+    }
+
+    #[test]
+    fn pledge_closes_when_crowdfund_ends() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_pledge(&mut contract, &mut context);
+
+        context.block_timestamp = ONE_DAY * 31;
+        testing_env!(context);
+        assert_eq!(1, contract.process_due_pledges(10));
+        let pledge = contract.get_pledge(0);
+        assert!(!pledge.active);
+        assert_eq!(0, pledge.released.0);
+        assert_eq!(0, contract.get_total_donations(0).0);
+    }
+
+    #[test]
+    fn frozen_pledges_do_not_block_the_crank() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_crowdfund(&mut contract);
+        add_sample_pledge(&mut contract, &mut context);
+        context.predecessor_account_id = "erin_near".to_string();
+        context.attached_deposit = ONE_NEAR * 10;
+        testing_env!(context.clone());
+        contract.create_pledge(1, U128(ONE_NEAR * 4), 7);
+
+        context.predecessor_account_id = "carol_near".to_string();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.set_crowdfund_frozen(0, true);
+        assert_eq!(1, contract.process_due_pledges(1));
+        assert_eq!(ONE_NEAR * 4, contract.get_pledge(1).released.0);
+        let pledge = contract.get_pledge(0);
+        assert_eq!(0, pledge.released.0);
+        assert_eq!(ONE_DAY * 7, pledge.next_due_at.0);
+        assert_eq!(0, contract.process_due_pledges(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PLEDGE_DONOR")]
+    fn cancel_pledge_by_stranger() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = new_contract("carol_near");
+        add_sample_crowdfund(&mut contract);
+        add_sample_pledge(&mut contract, &mut context);
+
+        context.predecessor_account_id = "erin_near".to_string();
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.cancel_pledge(0);
     }
 }
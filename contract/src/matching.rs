@@ -0,0 +1,303 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Opens a quadratic funding round for `duration_days`, returns its id
+    pub fn create_matching_round(&mut self, duration_days: u64, match_cap_bps: u16) -> u64 {
+        self.assert_owner();
+        require(
+            duration_days > 0 && duration_days <= self.config.max_duration_days,
+            ContractError::InvalidDuration,
+        );
+        require(
+            match_cap_bps > 0 && match_cap_bps as u128 <= BASIS_POINTS,
+            ContractError::InvalidMatchCap,
+        );
+        let round_id = self.matching_rounds.len();
+        let ends_at = env::block_timestamp() + duration_days * ONE_DAY;
+        self.matching_rounds.push(&MatchingRound {
+            id: round_id,
+            pool: 0,
+            ends_at,
+            match_cap_bps,
+            entries: vec![],
+            finalized: false,
+            unallocated: 0,
+        });
+        Event::MatchingRoundCreated {
+            round_id,
+            ends_at: U64(ends_at),
+            match_cap_bps,
+        }
+        .emit();
+        round_id
+    }
+
+    /// Adds the attached deposit to the matching pool of an open round, what the round does
+    /// not allocate can be reclaimed with `reclaim_matching_funds`
+    #[payable]
+    pub fn fund_matching_round(&mut self, round_id: u64) {
+        self.assert_not_paused();
+        let sponsor = env::predecessor_account_id();
+        let amount = env::attached_deposit();
+        require(amount > 0, ContractError::DepositTooSmall);
+        let mut round = self.internal_get_matching_round(round_id);
+        require(round.is_open(), ContractError::RoundClosed);
+        let initial_storage = env::storage_usage();
+        round.pool += amount;
+        self.matching_rounds.replace(round_id, &round);
+        let key = (round_id, sponsor.clone());
+        let mut round_sponsor = self.round_sponsors.get(&key).unwrap_or(RoundSponsor {
+            deposit: 0,
+            reclaimed: 0,
+        });
+        round_sponsor.deposit += amount;
+        self.round_sponsors.insert(&key, &round_sponsor);
+        self.internal_charge_storage(&sponsor, initial_storage);
+        Event::MatchingPoolFunded {
+            round_id,
+            sponsor,
+            amount: U128(amount),
+        }
+        .emit();
+    }
+
+    /// Opts an active crowdfund into a round, NEAR donations from then on count towards
+    /// its match
+    pub fn join_matching_round(&mut self, round_id: u64, id: u64) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_creator();
+        crowdfund.assert_accepting();
+        require(
+            crowdfund.matching_round.is_none(),
+            ContractError::AlreadyInRound,
+        );
+        let mut round = self.internal_get_matching_round(round_id);
+        require(round.is_open(), ContractError::RoundClosed);
+        require(
+            round.entries.len() < MAX_ROUND_CROWDFUNDS,
+            ContractError::RoundFull,
+        );
+        let initial_storage = env::storage_usage();
+        round.entries.push(RoundEntry::new(id));
+        crowdfund.matching_round = Some(round_id);
+        self.matching_rounds.replace(round_id, &round);
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&crowdfund.creator, initial_storage);
+        Event::MatchingRoundJoined {
+            round_id,
+            crowdfund_id: id,
+        }
+        .emit();
+    }
+
+    /// Splits the pool of an ended round and holds every crowdfund's share until
+    /// `settle_match`. Shares are capped, crowdfunds that already failed or were cancelled
+    /// take no part in the split and what is left of the pool is reclaimable by its sponsors
+    pub fn finalize_matching_round(&mut self, round_id: u64) {
+        self.assert_not_paused();
+        let mut round = self.internal_get_matching_round(round_id);
+        require(!round.finalized, ContractError::RoundClosed);
+        require(
+            env::block_timestamp() >= round.ends_at,
+            ContractError::RoundNotEnded,
+        );
+        let eligible: Vec<bool> = round
+            .entries
+            .iter()
+            .map(|entry| {
+                !self
+                    .internal_get_crowdfund(entry.crowdfund_id)
+                    .is_refundable()
+            })
+            .collect();
+        let total_match: u128 = round
+            .entries
+            .iter()
+            .zip(&eligible)
+            .filter(|(_, eligible)| **eligible)
+            .map(|(entry, _)| entry.quadratic_match())
+            .sum();
+        let match_cap = round.pool * round.match_cap_bps as u128 / BASIS_POINTS;
+        let mut matched: Money = 0;
+        for (entry, eligible) in round.entries.iter_mut().zip(eligible) {
+            if total_match == 0 || !eligible {
+                continue;
+            }
+            // split in parts per billion first, pool * match could overflow u128
+            let share = entry.quadratic_match() * MATCH_PRECISION / total_match;
+            let amount = std::cmp::min(round.pool / MATCH_PRECISION * share, match_cap);
+            entry.matched = amount;
+            matched += amount;
+        }
+        let unallocated = round.pool - matched;
+        round.unallocated += unallocated;
+        round.finalized = true;
+        self.matching_rounds.replace(round_id, &round);
+        Event::MatchingRoundFinalized {
+            round_id,
+            matched: U128(matched),
+            unallocated: U128(unallocated),
+        }
+        .emit();
+    }
+
+    /// Pays out the match held for a crowdfund in a finalized round, anyone may call it.
+    /// The match is credited to the crowdfund's escrow once it succeeded and every milestone
+    /// was approved, and returned if it failed or was cancelled instead
+    pub fn settle_match(&mut self, round_id: u64, id: u64) {
+        self.assert_not_paused();
+        let mut round = self.internal_get_matching_round(round_id);
+        require(round.finalized, ContractError::RoundNotEnded);
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        let entry = round
+            .entry_mut(id)
+            .filter(|entry| entry.matched > 0 && !entry.settled)
+            .unwrap_or_else(|| ContractError::NoMatch.panic());
+        let amount = entry.matched;
+        entry.settled = true;
+        if crowdfund.is_successful() && crowdfund.milestones_approved() {
+            crowdfund.total_donations += amount;
+            crowdfund.matched += amount;
+            self.stats.total_raised += amount;
+            self.internal_record_status(&mut crowdfund);
+            self.crowdfunds.replace(id, &crowdfund);
+            self.matching_rounds.replace(round_id, &round);
+            Event::MatchCredited {
+                round_id,
+                crowdfund_id: id,
+                amount: U128(amount),
+            }
+            .emit();
+        } else {
+            require(crowdfund.is_refundable(), ContractError::MatchPending);
+            self.matching_rounds.replace(round_id, &round);
+            self.internal_return_match(round_id, id, amount);
+        }
+    }
+
+    /// Sends the caller their pro rata share of the pool a finalized round did not allocate.
+    /// Matches returned later by failed crowdfunds can be reclaimed by calling it again
+    pub fn reclaim_matching_funds(&mut self, round_id: u64) -> Promise {
+        self.assert_not_paused();
+        let sponsor = env::predecessor_account_id();
+        let round = self.internal_get_matching_round(round_id);
+        require(round.finalized, ContractError::RoundNotEnded);
+        let key = (round_id, sponsor.clone());
+        let mut round_sponsor = self
+            .round_sponsors
+            .get(&key)
+            .unwrap_or_else(|| ContractError::NothingToReclaim.panic());
+        let amount = round_sponsor.reclaimable(&round);
+        require(amount > 0, ContractError::NothingToReclaim);
+        round_sponsor.reclaimed += amount;
+        self.round_sponsors.insert(&key, &round_sponsor);
+        Event::MatchingFundsReclaimed {
+            round_id,
+            sponsor: sponsor.clone(),
+            amount: U128(amount),
+        }
+        .emit();
+        Promise::new(sponsor).transfer(amount)
+    }
+
+    pub fn get_matching_round(&self, round_id: u64) -> MatchingRoundView {
+        self.internal_get_matching_round(round_id).into()
+    }
+
+    pub fn list_matching_rounds(&self, from_index: u64, limit: u64) -> Vec<MatchingRoundView> {
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.matching_rounds.len());
+        (from_index..to_index)
+            .map(|index| self.matching_rounds.get(index).unwrap().into())
+            .collect()
+    }
+}
+
+impl Contract {
+    fn internal_get_matching_round(&self, round_id: u64) -> MatchingRound {
+        self.matching_rounds
+            .get(round_id)
+            .unwrap_or_else(|| ContractError::RoundNotFound.panic())
+    }
+
+    /// Counts a NEAR donation towards the round the crowdfund joined, while it is open
+    pub(crate) fn internal_record_round_contribution(
+        &mut self,
+        crowdfund: &Crowdfund,
+        donor: &AccountId,
+        amount: Money,
+    ) {
+        let round_id = match crowdfund.matching_round {
+            Some(round_id) => round_id,
+            None => return,
+        };
+        let mut round = self.internal_get_matching_round(round_id);
+        if !round.is_open() {
+            return;
+        }
+        let key = (round_id, crowdfund.id, donor.clone());
+        let previous = self.round_contributions.get(&key).unwrap_or(0);
+        let total = previous + amount;
+        self.round_contributions.insert(&key, &total);
+
+        let entry = round.entry_mut(crowdfund.id).unwrap();
+        entry.contributions += amount;
+        entry.sum_sqrt =
+            entry.sum_sqrt + integer_sqrt(total / QF_UNIT) - integer_sqrt(previous / QF_UNIT);
+        self.matching_rounds.replace(round_id, &round);
+    }
+
+    /// Takes refunded NEAR out of the tally of a round that was not finalized yet, so
+    /// donations that were given back earn no match
+    pub(crate) fn internal_remove_round_contribution(
+        &mut self,
+        crowdfund: &Crowdfund,
+        donor: &AccountId,
+        amount: Money,
+    ) {
+        let round_id = match crowdfund.matching_round {
+            Some(round_id) => round_id,
+            None => return,
+        };
+        let mut round = self.internal_get_matching_round(round_id);
+        if round.finalized {
+            return;
+        }
+        let key = (round_id, crowdfund.id, donor.clone());
+        let previous = match self.round_contributions.get(&key) {
+            Some(previous) => previous,
+            None => return,
+        };
+        // donations made before the crowdfund joined were never counted
+        let removed = std::cmp::min(previous, amount);
+        let total = previous - removed;
+        self.round_contributions.insert(&key, &total);
+
+        let entry = round.entry_mut(crowdfund.id).unwrap();
+        entry.contributions -= removed;
+        entry.sum_sqrt =
+            entry.sum_sqrt + integer_sqrt(total / QF_UNIT) - integer_sqrt(previous / QF_UNIT);
+        self.matching_rounds.replace(round_id, &round);
+    }
+
+    /// Gives back to the round's sponsors a match that will not be paid out to the
+    /// crowdfund it was held for
+    pub(crate) fn internal_return_match(
+        &mut self,
+        round_id: u64,
+        crowdfund_id: u64,
+        amount: Money,
+    ) {
+        let mut round = self.internal_get_matching_round(round_id);
+        round.unallocated += amount;
+        self.matching_rounds.replace(round_id, &round);
+        Event::MatchReturned {
+            round_id,
+            crowdfund_id,
+            amount: U128(amount),
+        }
+        .emit();
+    }
+}
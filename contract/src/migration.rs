@@ -0,0 +1,133 @@
+use crate::*;
+
+/// Key the contract struct is persisted under
+const STATE_KEY: &[u8] = b"STATE";
+/// Crowdfund id of migrated donations, the first layout did not record it
+pub const LEGACY_CROWDFUND_ID: u64 = u64::MAX;
+/// Campaign length given to migrated crowdfunds, the first layout had no deadline
+pub const LEGACY_CAMPAIGN_DAYS: u64 = 30;
+
+/// Crowdfund as stored by the first contract layout
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyCrowdfund {
+    pub id: i32,
+    pub creator: AccountId,
+    pub created_at: Timestamp,
+    pub title: String,
+    /// in NEAR, the first layout did not convert it to yocto
+    pub donation_target: u128,
+    pub total_donations: u128,
+    pub total_votes: i64,
+    pub description: String,
+    pub votes: Vec<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyDonation {
+    pub amount: Money,
+    pub donor: AccountId,
+}
+
+/// First contract layout, kept everything in plain vectors and had no version tag
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyContract {
+    pub owner: AccountId,
+    pub crowdfunds: Vec<LegacyCrowdfund>,
+    pub donations: Vec<LegacyDonation>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Converts the persisted state to the current layout. Runs after `upgrade` deployed
+    /// new code, or when the owner deploys with `--initFunction migrate`
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state =
+            env::storage_read(STATE_KEY).unwrap_or_else(|| ContractError::StateNotFound.panic());
+        let contract = match Contract::try_from_slice(&state) {
+            Ok(contract) => contract,
+            Err(_) => {
+                // the first layout had no version tag
+                let legacy = LegacyContract::try_from_slice(&state)
+                    .unwrap_or_else(|_| ContractError::UnknownStateVersion.panic());
+                assert_owner_or_self(&legacy.owner);
+                return Contract::internal_migrate_legacy(legacy);
+            }
+        };
+        assert_owner_or_self(&contract.owner);
+        match contract.state_version {
+            STATE_VERSION => contract,
+            _ => ContractError::UnknownStateVersion.panic(),
+        }
+    }
+
+    /// Deploys the wasm passed as raw call input and migrates the state in the same
+    /// transaction, so the contract never runs new code on an old layout
+    pub fn upgrade(&self) -> Promise {
+        self.assert_owner();
+        let code = env::input().unwrap_or_else(|| ContractError::MissingCode.panic());
+        require(!code.is_empty(), ContractError::MissingCode);
+        let migrate_gas = env::prepaid_gas()
+            .checked_sub(env::used_gas())
+            .and_then(|gas| gas.checked_sub(UPGRADE_GAS))
+            .unwrap_or_else(|| ContractError::NotEnoughGas.panic());
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), vec![], 0, migrate_gas)
+    }
+
+    pub fn get_state_version(&self) -> u16 {
+        self.state_version
+    }
+}
+
+/// `migrate` is called by the contract itself from `upgrade`, or by the owner directly
+fn assert_owner_or_self(owner: &AccountId) {
+    let caller = env::predecessor_account_id();
+    require(
+        &caller == owner || caller == env::current_account_id(),
+        ContractError::NotOwner,
+    );
+}
+
+impl Contract {
+    /// The first layout sent donations straight back instead of holding them, so migrated
+    /// crowdfunds start with nothing raised and their donations are kept as settled history
+    fn internal_migrate_legacy(legacy: LegacyContract) -> Self {
+        let mut contract = Contract::internal_new(legacy.owner);
+        let deadline = env::block_timestamp() + LEGACY_CAMPAIGN_DAYS * ONE_DAY;
+        for (index, old) in legacy.crowdfunds.into_iter().enumerate() {
+            let id = index as u64;
+            let mut crowdfund = Crowdfund::new(
+                id,
+                old.title,
+                toYocto(old.donation_target),
+                old.description,
+                deadline,
+                vec![],
+            );
+            crowdfund.creator = old.creator;
+            crowdfund.created_at = old.created_at;
+            contract.internal_add_crowdfund(&crowdfund);
+            let mut votes = contract.votes_by_crowdfund.get(&id).unwrap();
+            for voter in &old.votes {
+                votes.insert(voter);
+            }
+            contract.votes_by_crowdfund.insert(&id, &votes);
+        }
+        for old in legacy.donations {
+            contract.internal_add_donation(Donation::migrated(
+                LEGACY_CROWDFUND_ID,
+                old.donor,
+                old.amount,
+            ));
+        }
+        Event::StateMigrated {
+            state_version: STATE_VERSION,
+            crowdfunds: contract.crowdfunds.len(),
+            donations: contract.donations.len(),
+        }
+        .emit();
+        contract
+    }
+}
@@ -0,0 +1,169 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Opens the backers' vote on the current milestone of a successful crowdfund, anyone may
+    /// once the milestone is due
+    pub fn start_milestone_vote(&mut self, id: u64) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        require(crowdfund.is_successful(), ContractError::TargetNotReached);
+        let index = crowdfund
+            .current_milestone()
+            .unwrap_or_else(|| ContractError::MilestoneNotDue.panic());
+        let now = env::block_timestamp();
+        let milestone = &mut crowdfund.milestones[index];
+        require(
+            milestone.status == MilestoneStatus::Pending && milestone.due_date <= now,
+            ContractError::MilestoneNotDue,
+        );
+        milestone.status = MilestoneStatus::Voting;
+        milestone.voting_ends_at = now + MILESTONE_VOTING_PERIOD;
+        let voting_ends_at = milestone.voting_ends_at;
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(id, &crowdfund);
+        Event::MilestoneVoteStarted {
+            crowdfund_id: id,
+            milestone: index as u64,
+            voting_ends_at: U64(voting_ends_at),
+        }
+        .emit();
+    }
+
+    /// Approves or rejects the milestone under vote, weighted by what the caller donated in
+    /// NEAR and tokens
+    pub fn vote_milestone(&mut self, id: u64, approve: bool) {
+        self.assert_not_paused();
+        let voter = env::predecessor_account_id();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        let index = crowdfund
+            .current_milestone()
+            .unwrap_or_else(|| ContractError::VotingClosed.panic());
+        let weight = self.internal_contribution(&crowdfund, &voter);
+        let milestone = &mut crowdfund.milestones[index];
+        require(
+            milestone.status == MilestoneStatus::Voting
+                && env::block_timestamp() < milestone.voting_ends_at,
+            ContractError::VotingClosed,
+        );
+        require(weight > 0, ContractError::NotBacker);
+        let initial_storage = env::storage_usage();
+        require(
+            self.milestone_votes
+                .insert(&(id, index as u64, voter.clone())),
+            ContractError::AlreadyVoted,
+        );
+        if approve {
+            milestone.approval_weight += weight;
+        } else {
+            milestone.rejection_weight += weight;
+        }
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&voter, initial_storage);
+        Event::MilestoneVoteCast {
+            crowdfund_id: id,
+            milestone: index as u64,
+            voter,
+            approve,
+            weight: U128(weight),
+        }
+        .emit();
+    }
+
+    /// Closes the vote on the current milestone once it ended. It passes when approvals
+    /// outweigh rejections and at least `MILESTONE_QUORUM_BPS` of the donations voted,
+    /// otherwise the crowdfund fails and backers can claim back what is still locked,
+    /// NEAR and tokens alike
+    pub fn finalize_milestone(&mut self, id: u64) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_not_frozen();
+        let index = crowdfund
+            .current_milestone()
+            .unwrap_or_else(|| ContractError::VotingClosed.panic());
+        let voting_weight = crowdfund.total_vote_weight();
+        let milestone = &mut crowdfund.milestones[index];
+        require(
+            milestone.status == MilestoneStatus::Voting,
+            ContractError::VotingClosed,
+        );
+        require(
+            env::block_timestamp() >= milestone.voting_ends_at,
+            ContractError::VotingNotEnded,
+        );
+        let turnout = milestone.approval_weight + milestone.rejection_weight;
+        let approved = milestone.approval_weight > milestone.rejection_weight
+            && turnout * BASIS_POINTS >= voting_weight * MILESTONE_QUORUM_BPS;
+        if approved {
+            milestone.status = MilestoneStatus::Approved;
+        } else {
+            milestone.status = MilestoneStatus::Rejected;
+            // token-only crowdfunds raised no NEAR, their tokens are refunded in full
+            if crowdfund.total_donations > 0 {
+                let locked = crowdfund.total_donations - crowdfund.unlocked();
+                crowdfund.refund_bps = (locked * BASIS_POINTS / crowdfund.total_donations) as u16;
+            }
+            crowdfund.milestone_rejected = true;
+        }
+        self.internal_record_status(&mut crowdfund);
+        self.crowdfunds.replace(id, &crowdfund);
+        Event::MilestoneFinalized {
+            crowdfund_id: id,
+            milestone: index as u64,
+            approved,
+        }
+        .emit();
+    }
+
+    pub fn get_milestones(&self, id: u64) -> Vec<MilestoneView> {
+        self.internal_get_crowdfund(id)
+            .milestones
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Checks the milestones given to `add_crowdfund`, they must split the whole target and
+    /// fall due in order after the campaign ends
+    pub(crate) fn internal_milestones(
+        &self,
+        inputs: Vec<MilestoneInput>,
+        deadline: Timestamp,
+    ) -> Vec<Milestone> {
+        if inputs.is_empty() {
+            return vec![];
+        }
+        let total_bps: u128 = inputs.iter().map(|input| input.share_bps as u128).sum();
+        let mut previous_due_date = deadline;
+        for input in &inputs {
+            require(
+                input.share_bps > 0 && input.due_date.0 >= previous_due_date,
+                ContractError::InvalidMilestones,
+            );
+            previous_due_date = input.due_date.0;
+        }
+        require(
+            inputs.len() <= MAX_MILESTONES && total_bps == BASIS_POINTS,
+            ContractError::InvalidMilestones,
+        );
+        inputs.into_iter().map(Milestone::new).collect()
+    }
+
+    /// What the donor gave to a crowdfund and did not get back, their weight in milestone votes
+    pub(crate) fn internal_contribution(&self, crowdfund: &Crowdfund, donor: &AccountId) -> Money {
+        self.donations_by_donor
+            .get(donor)
+            .map_or(0, |donor_donations| {
+                donor_donations
+                    .iter()
+                    .map(|donation_id| self.donations.get(donation_id).unwrap())
+                    .filter(|donation| donation.crowdfund_id == crowdfund.id && !donation.refunded)
+                    .map(|donation| crowdfund.vote_weight(&donation.token_id, donation.amount))
+                    .sum()
+            })
+    }
+}
@@ -3,47 +3,857 @@ use near_sdk::serde::{Deserialize, Serialize};
 #[allow(unused_imports)]
 use near_sdk::{env, near_bindgen};
 
-use crate::utils::{AccountId, Money, Timestamp};
-#[derive(Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+use near_sdk::json_types::{U128, U64};
+
+use crate::errors::{require, ContractError};
+use crate::utils::{
+    is_valid_media, AccountId, Money, Timestamp, BASIS_POINTS, DEFAULT_MAX_DURATION_DAYS,
+    MATCH_PRECISION, MAX_DESCRIPTION_LENGTH, MAX_TITLE_LENGTH, QF_UNIT,
+};
+use crate::views::{CrowdfundPayout, CrowdfundSummary, CrowdfundView};
+
+/// Platform-wide parameters set by the contract owner
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct PlatformConfig {
+    /// smallest accepted donation, in yocto
+    pub min_donation: Money,
+    pub max_duration_days: u64,
+    /// platform fee charged on NEAR withdrawals, in basis points
+    pub fee_bps: u16,
+}
+
+impl Default for PlatformConfig {
+    fn default() -> Self {
+        PlatformConfig {
+            min_donation: 1,
+            max_duration_days: DEFAULT_MAX_DURATION_DAYS,
+            fee_bps: 0,
+        }
+    }
+}
+
+impl PlatformConfig {
+    pub fn fee(&self, amount: Money) -> Money {
+        amount * self.fee_bps as u128 / BASIS_POINTS
+    }
+}
+
+/// Lifecycle of a crowdfund, derived from its deadline and bookkeeping
+#[derive(
+    Clone, Copy, Debug, PartialEq, Serialize, Deserialize, BorshDeserialize, BorshSerialize,
+)]
 #[serde(crate = "near_sdk::serde")]
+pub enum CrowdfundStatus {
+    Active,
+    Succeeded,
+    Failed,
+    Cancelled,
+    Withdrawn,
+}
+
+/// Running totals behind `get_contract_stats`, amounts are in yocto
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+pub struct PlatformStats {
+    pub active_crowdfunds: u64,
+    pub successful_crowdfunds: u64,
+    /// NEAR donated and matched, before fees and refunds
+    pub total_raised: Money,
+    pub total_withdrawn: Money,
+    pub total_refunded: Money,
+}
+
+impl PlatformStats {
+    /// Moves a crowdfund from the count of its previous status to that of its current one
+    pub fn record_status(&mut self, from: CrowdfundStatus, to: CrowdfundStatus) {
+        match from {
+            CrowdfundStatus::Active => self.active_crowdfunds -= 1,
+            CrowdfundStatus::Succeeded | CrowdfundStatus::Withdrawn => {
+                self.successful_crowdfunds -= 1
+            }
+            CrowdfundStatus::Failed | CrowdfundStatus::Cancelled => {}
+        }
+        match to {
+            CrowdfundStatus::Active => self.active_crowdfunds += 1,
+            CrowdfundStatus::Succeeded | CrowdfundStatus::Withdrawn => {
+                self.successful_crowdfunds += 1
+            }
+            CrowdfundStatus::Failed | CrowdfundStatus::Cancelled => {}
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Serialize, Deserialize, BorshDeserialize, BorshSerialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum MilestoneStatus {
+    Pending,
+    Voting,
+    Approved,
+    Rejected,
+}
+
+/// Milestone as given by the creator to `add_crowdfund`
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MilestoneInput {
+    pub title: String,
+    /// part of the NEAR raised released by this milestone, in basis points
+    pub share_bps: u16,
+    pub due_date: U64,
+}
+
+/// Stage of a crowdfund whose share of the escrow is released once backers approve it
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct Milestone {
+    pub title: String,
+    pub share_bps: u16,
+    pub due_date: Timestamp,
+    pub status: MilestoneStatus,
+    pub voting_ends_at: Timestamp,
+    /// approvals and rejections, weighted by each backer's contribution in NEAR and tokens
+    pub approval_weight: Money,
+    pub rejection_weight: Money,
+}
+
+impl Milestone {
+    pub fn new(input: MilestoneInput) -> Self {
+        Milestone {
+            title: input.title,
+            share_bps: input.share_bps,
+            due_date: input.due_date.0,
+            status: MilestoneStatus::Pending,
+            voting_ends_at: 0,
+            approval_weight: 0,
+            rejection_weight: 0,
+        }
+    }
+}
+
+/// Orderings offered by `list_crowdfunds_sorted`, largest or most recent first
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CrowdfundSort {
+    CreatedAt,
+    TotalVotes,
+    TotalDonations,
+}
+
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
 pub struct Crowdfund {
-    id: i32,
+    pub id: u64,
     pub creator: AccountId,
-    created_at: Timestamp,
-    title: String,
-    donation_target: u128,
+    pub created_at: Timestamp,
+    pub deadline: Timestamp,
+    pub title: String,
+    pub donation_target: u128,
     pub total_donations: u128,
-    pub total_votes: i64,
+    pub withdrawn: u128,
+    /// platform fee kept from the NEAR withdrawn so far
+    pub fees: u128,
+    pub refunded: u128,
+    /// position in the crowdfund's donation index up to which refunds were pushed
+    pub refund_cursor: u64,
     description: String,
-    pub votes: Vec<String>,
+    /// cover image or video, an http(s) URL or an IPFS CID
+    pub media: Option<String>,
+    pub cancelled: bool,
+    /// why the crowdfund was cancelled, shown to its donors
+    pub cancel_reason: Option<String>,
+    pub delisted: bool,
+    pub frozen: bool,
+    /// fungible tokens the creator takes donations in, each must be whitelisted by the owner
+    pub accepted_tokens: Vec<AccountId>,
+    pub token_escrows: Vec<TokenEscrow>,
+    /// when not empty, the NEAR raised is released milestone by milestone
+    pub milestones: Vec<Milestone>,
+    /// set once backers reject a milestone, the locked escrow goes back to them
+    pub milestone_rejected: bool,
+    /// part of each NEAR donation paid back on refund, below 100% once milestones were released
+    pub refund_bps: u16,
+    /// perks offered to backers, a tier's id is its index
+    pub reward_tiers: Vec<RewardTier>,
+    /// quadratic funding round the crowdfund joined, its NEAR donations count towards matching
+    pub matching_round: Option<u64>,
+    /// NEAR credited from matching pools, already part of `total_donations`
+    pub matched: Money,
+    /// bumped on every edit, earlier versions are kept as `CrowdfundRevision`s
+    pub version: u64,
+    /// status the crowdfund is counted under in `PlatformStats`, it lags `status()` until
+    /// a transaction touches the crowdfund
+    pub counted_status: CrowdfundStatus,
 }
 
 impl Crowdfund {
-    pub fn new(id: i32, title: String, donation_target: u128, description: String) -> Self {
+    pub fn new(
+        id: u64,
+        title: String,
+        donation_target: u128,
+        description: String,
+        deadline: Timestamp,
+        milestones: Vec<Milestone>,
+    ) -> Self {
         Crowdfund {
             id,
-            creator: env::signer_account_id(),
+            creator: env::predecessor_account_id(),
             created_at: env::block_timestamp(),
+            deadline,
             title,
             donation_target,
             total_donations: 0,
-            total_votes: 0,
+            withdrawn: 0,
+            fees: 0,
+            refunded: 0,
+            refund_cursor: 0,
             description,
-            votes: vec![],
+            media: None,
+            cancelled: false,
+            cancel_reason: None,
+            delisted: false,
+            frozen: false,
+            accepted_tokens: vec![],
+            token_escrows: vec![],
+            milestones,
+            milestone_rejected: false,
+            refund_bps: BASIS_POINTS as u16,
+            reward_tiers: vec![],
+            matching_round: None,
+            matched: 0,
+            version: 0,
+            counted_status: CrowdfundStatus::Active,
+        }
+    }
+
+    pub fn status(&self) -> CrowdfundStatus {
+        if self.cancelled {
+            CrowdfundStatus::Cancelled
+        } else if self.milestone_rejected {
+            CrowdfundStatus::Failed
+        } else if self.withdrawn > 0 {
+            CrowdfundStatus::Withdrawn
+        } else if env::block_timestamp() < self.deadline {
+            CrowdfundStatus::Active
+        } else if self.progress_bps() >= BASIS_POINTS {
+            CrowdfundStatus::Succeeded
+        } else {
+            CrowdfundStatus::Failed
         }
     }
+
+    /// Checks what the creator typed in, on creation and on every edit
+    pub fn assert_valid(&self) {
+        require(
+            !self.title.trim().is_empty() && self.title.len() <= MAX_TITLE_LENGTH,
+            ContractError::InvalidTitle,
+        );
+        require(
+            !self.description.trim().is_empty() && self.description.len() <= MAX_DESCRIPTION_LENGTH,
+            ContractError::InvalidDescription,
+        );
+        require(self.donation_target > 0, ContractError::InvalidTarget);
+        if let Some(media) = &self.media {
+            require(is_valid_media(media), ContractError::InvalidMedia);
+        }
+    }
+
+    /// Share of the donation target raised so far, in percent
+    pub fn progress(&self) -> u128 {
+        self.progress_bps() / 100
+    }
+
+    /// Share of the donation target raised so far in basis points. Every token escrow adds
+    /// its own share of its token target, so token-only campaigns can succeed too
+    pub fn progress_bps(&self) -> u128 {
+        if self.donation_target == 0 {
+            return BASIS_POINTS;
+        }
+        let near_bps = self.total_donations * BASIS_POINTS / self.donation_target;
+        let token_bps: u128 = self
+            .token_escrows
+            .iter()
+            .filter(|escrow| escrow.target > 0)
+            .map(|escrow| escrow.total_donations * BASIS_POINTS / escrow.target)
+            .sum();
+        near_bps + token_bps
+    }
+
+    /// NEAR raised and how it splits between the platform and the creator, fees not
+    /// charged yet are projected with the current `config`
+    pub fn payout(&self, config: &PlatformConfig) -> CrowdfundPayout {
+        let fees = self.fees + config.fee(self.total_donations - self.withdrawn);
+        CrowdfundPayout {
+            gross_raised: U128(self.total_donations),
+            fees: U128(fees),
+            net_payout: U128(self.total_donations - fees),
+        }
+    }
+
+    /// Applies an edit of the descriptive fields and returns the version it replaced.
+    /// The target may move, but never below what was already raised
+    pub fn edit(
+        &mut self,
+        title: Option<String>,
+        description: Option<String>,
+        donation_target: Option<u128>,
+        media: Option<String>,
+    ) -> CrowdfundRevision {
+        require(
+            title.is_some()
+                || description.is_some()
+                || donation_target.is_some()
+                || media.is_some(),
+            ContractError::NothingToEdit,
+        );
+        if let Some(donation_target) = donation_target {
+            require(
+                donation_target > 0 && donation_target >= self.total_donations,
+                ContractError::InvalidTarget,
+            );
+        }
+        let revision = CrowdfundRevision {
+            version: self.version,
+            title: self.title.clone(),
+            description: self.description.clone(),
+            donation_target: self.donation_target,
+            media: self.media.clone(),
+            replaced_at: env::block_timestamp(),
+        };
+        self.title = title.unwrap_or_else(|| self.title.clone());
+        self.description = description.unwrap_or_else(|| self.description.clone());
+        self.donation_target = donation_target.unwrap_or(self.donation_target);
+        if media.is_some() {
+            self.media = media;
+        }
+        self.version += 1;
+        self.assert_valid();
+        revision
+    }
+
+    pub fn view(&self, total_votes: u64) -> CrowdfundView {
+        CrowdfundView {
+            id: self.id,
+            creator: self.creator.clone(),
+            created_at: U64(self.created_at),
+            deadline: U64(self.deadline),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            media: self.media.clone(),
+            donation_target: U128(self.donation_target),
+            total_donations: U128(self.total_donations),
+            withdrawn: U128(self.withdrawn),
+            refunded: U128(self.refunded),
+            matched: U128(self.matched),
+            total_votes,
+            status: self.status(),
+            cancel_reason: self.cancel_reason.clone(),
+            accepted_tokens: self.accepted_tokens.clone(),
+            milestones: self.milestones.iter().cloned().map(Into::into).collect(),
+            reward_tiers: self.reward_tiers.iter().cloned().map(Into::into).collect(),
+            matching_round: self.matching_round,
+            version: self.version,
+        }
+    }
+
+    pub fn summary(&self, total_votes: u64) -> CrowdfundSummary {
+        CrowdfundSummary {
+            id: self.id,
+            creator: self.creator.clone(),
+            created_at: U64(self.created_at),
+            deadline: U64(self.deadline),
+            title: self.title.clone(),
+            media: self.media.clone(),
+            donation_target: U128(self.donation_target),
+            total_donations: U128(self.total_donations),
+            total_votes,
+            status: self.status(),
+        }
+    }
+
+    /// Donors get their money back from failed and cancelled campaigns
+    pub fn is_refundable(&self) -> bool {
+        matches!(
+            self.status(),
+            CrowdfundStatus::Failed | CrowdfundStatus::Cancelled
+        )
+    }
+
+    /// Withdrawals stay open after the first one so every token can be collected
+    pub fn is_successful(&self) -> bool {
+        matches!(
+            self.status(),
+            CrowdfundStatus::Succeeded | CrowdfundStatus::Withdrawn
+        )
+    }
+
+    /// Campaigns can be cancelled until the creator received any funds
+    pub fn is_cancellable(&self) -> bool {
+        matches!(
+            self.status(),
+            CrowdfundStatus::Active | CrowdfundStatus::Succeeded
+        ) && self
+            .token_escrows
+            .iter()
+            .all(|escrow| escrow.withdrawn == 0)
+    }
+
+    pub fn is_accepting(&self) -> bool {
+        !self.frozen && !self.delisted && self.status() == CrowdfundStatus::Active
+    }
+
+    pub fn accepts_token(&self, token_id: &AccountId) -> bool {
+        self.accepted_tokens.contains(token_id)
+    }
+
+    pub fn token_escrow(&self, token_id: &AccountId) -> Option<&TokenEscrow> {
+        self.token_escrows
+            .iter()
+            .find(|escrow| &escrow.token_id == token_id)
+    }
+
+    pub fn token_escrow_mut(&mut self, token_id: &AccountId) -> &mut TokenEscrow {
+        let index = match self
+            .token_escrows
+            .iter()
+            .position(|escrow| &escrow.token_id == token_id)
+        {
+            Some(index) => index,
+            None => {
+                self.token_escrows.push(TokenEscrow::new(token_id.clone()));
+                self.token_escrows.len() - 1
+            }
+        };
+        &mut self.token_escrows[index]
+    }
+
+    /// NEAR released to the creator, all of it unless milestones are pending
+    pub fn unlocked(&self) -> Money {
+        if self.milestones_approved() {
+            return self.total_donations;
+        }
+        let approved_bps: u128 = self
+            .milestones
+            .iter()
+            .filter(|milestone| milestone.status == MilestoneStatus::Approved)
+            .map(|milestone| milestone.share_bps as u128)
+            .sum();
+        self.total_donations * approved_bps / BASIS_POINTS
+    }
+
+    /// Index of the first milestone not approved yet
+    pub fn current_milestone(&self) -> Option<usize> {
+        self.milestones
+            .iter()
+            .position(|milestone| milestone.status != MilestoneStatus::Approved)
+    }
+
+    /// Also holds for crowdfunds without milestones
+    pub fn milestones_approved(&self) -> bool {
+        self.milestones
+            .iter()
+            .all(|milestone| milestone.status == MilestoneStatus::Approved)
+    }
+
+    /// Weight of a donation in milestone votes. Tokens count as their share of the token
+    /// target applied to the donation target, so every backer votes in NEAR
+    pub fn vote_weight(&self, token_id: &Option<AccountId>, amount: Money) -> Money {
+        match token_id {
+            None => amount,
+            Some(token_id) => match self.token_escrow(token_id) {
+                Some(escrow) if escrow.target > 0 => {
+                    let share = amount / escrow.target * MATCH_PRECISION
+                        + amount % escrow.target * MATCH_PRECISION / escrow.target;
+                    self.donation_target / MATCH_PRECISION * share
+                }
+                _ => 0,
+            },
+        }
+    }
+
+    /// Weight of every vote that could be cast on a milestone. Matched NEAR carries no vote
+    pub fn total_vote_weight(&self) -> Money {
+        let token_weight: Money = self
+            .token_escrows
+            .iter()
+            .map(|escrow| {
+                self.vote_weight(
+                    &Some(escrow.token_id.clone()),
+                    escrow.total_donations - escrow.refunded,
+                )
+            })
+            .sum();
+        self.total_donations - self.matched + token_weight
+    }
+
+    /// Refund owed for a donation, NEAR refunds shrink by what milestones already released
+    pub fn refund_for(&self, token_id: &Option<AccountId>, amount: Money) -> Money {
+        match token_id {
+            None => amount * self.refund_bps as u128 / BASIS_POINTS,
+            Some(_) => amount,
+        }
+    }
+
+    /// Escrowed amount the creator may withdraw now, in NEAR (`None`) or a token
+    pub fn withdrawable(&self, token_id: &Option<AccountId>) -> Money {
+        match token_id {
+            None => self.unlocked() - self.withdrawn,
+            Some(token_id) => self
+                .token_escrow(token_id)
+                .map_or(0, |escrow| escrow.total_donations - escrow.withdrawn),
+        }
+    }
+
+    pub fn record_withdrawal(&mut self, token_id: &Option<AccountId>, amount: Money) {
+        match token_id {
+            None => self.withdrawn += amount,
+            Some(token_id) => self.token_escrow_mut(token_id).withdrawn += amount,
+        }
+    }
+
+    pub fn revert_withdrawal(&mut self, token_id: &Option<AccountId>, amount: Money) {
+        match token_id {
+            None => self.withdrawn -= amount,
+            Some(token_id) => self.token_escrow_mut(token_id).withdrawn -= amount,
+        }
+    }
+
+    pub fn record_refund(&mut self, token_id: &Option<AccountId>, amount: Money) {
+        match token_id {
+            None => self.refunded += amount,
+            Some(token_id) => self.token_escrow_mut(token_id).refunded += amount,
+        }
+    }
+
+    pub fn revert_refund(&mut self, token_id: &Option<AccountId>, amount: Money) {
+        match token_id {
+            None => self.refunded -= amount,
+            Some(token_id) => self.token_escrow_mut(token_id).refunded -= amount,
+        }
+    }
+
+    pub fn assert_creator(&self) {
+        require(
+            self.creator == env::predecessor_account_id(),
+            ContractError::NotCreator,
+        );
+    }
+
+    /// Frozen crowdfunds reject every change until the owner unfreezes them
+    pub fn assert_not_frozen(&self) {
+        require(!self.frozen, ContractError::CrowdfundFrozen);
+    }
+
+    /// Donations and votes are only taken by listed, active crowdfunds
+    pub fn assert_accepting(&self) {
+        self.assert_not_frozen();
+        require(!self.delisted, ContractError::CrowdfundDelisted);
+        self.assert_status(CrowdfundStatus::Active, ContractError::CampaignClosed);
+    }
+
+    pub fn assert_status(&self, status: CrowdfundStatus, error: ContractError) {
+        require(self.status() == status, error);
+    }
+
+    pub fn reward_tier_mut(&mut self, tier_id: u64) -> &mut RewardTier {
+        self.reward_tiers
+            .get_mut(tier_id as usize)
+            .unwrap_or_else(|| ContractError::RewardTierNotFound.panic())
+    }
+
+    /// Takes one unit of a tier for a NEAR donation of `amount`
+    pub fn claim_reward_tier(&mut self, tier_id: u64, amount: Money) {
+        let tier = self.reward_tier_mut(tier_id);
+        require(
+            amount >= tier.min_pledge,
+            ContractError::PledgeBelowTierMinimum,
+        );
+        require(tier.remaining() > 0, ContractError::RewardTierSoldOut);
+        tier.claimed += 1;
+    }
+}
+
+/// Reward tier as given by the creator, `quantity` is unlimited when `None`
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardTierInput {
+    pub min_pledge: U128,
+    pub description: String,
+    pub quantity: Option<u64>,
+    pub estimated_delivery: U64,
+}
+
+/// Perk promised to backers who pledge at least `min_pledge` yocto
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct RewardTier {
+    pub id: u64,
+    pub min_pledge: Money,
+    pub description: String,
+    pub quantity: Option<u64>,
+    /// donations that selected this tier
+    pub claimed: u64,
+    pub estimated_delivery: Timestamp,
+}
+
+impl RewardTier {
+    pub fn new(id: u64, input: RewardTierInput) -> Self {
+        RewardTier {
+            id,
+            min_pledge: input.min_pledge.0,
+            description: input.description,
+            quantity: input.quantity,
+            claimed: 0,
+            estimated_delivery: input.estimated_delivery.0,
+        }
+    }
+
+    /// Edits the tier, a limited quantity cannot drop below what backers already claimed
+    pub fn update(&mut self, input: RewardTierInput) {
+        require(
+            input
+                .quantity
+                .map_or(true, |quantity| quantity >= self.claimed),
+            ContractError::InvalidRewardTier,
+        );
+        self.min_pledge = input.min_pledge.0;
+        self.description = input.description;
+        self.quantity = input.quantity;
+        self.estimated_delivery = input.estimated_delivery.0;
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.quantity
+            .map_or(u64::MAX, |quantity| quantity - self.claimed)
+    }
+}
+/// Quadratic funding round. Sponsors fill the pool, which is split between the crowdfunds
+/// that joined by the square of the summed square roots of their backers' contributions
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct MatchingRound {
+    pub id: u64,
+    pub pool: Money,
+    pub ends_at: Timestamp,
+    /// most of the pool a single crowdfund may receive, in basis points
+    pub match_cap_bps: u16,
+    pub entries: Vec<RoundEntry>,
+    pub finalized: bool,
+    /// part of the pool no crowdfund receives, sponsors reclaim it pro rata
+    pub unallocated: Money,
+}
+
+impl MatchingRound {
+    pub fn is_open(&self) -> bool {
+        !self.finalized && env::block_timestamp() < self.ends_at
+    }
+
+    pub fn entry_mut(&mut self, crowdfund_id: u64) -> Option<&mut RoundEntry> {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.crowdfund_id == crowdfund_id)
+    }
+}
+
+/// Deposit of one sponsor to a matching pool
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RoundSponsor {
+    pub deposit: Money,
+    /// unallocated funds already sent back to the sponsor
+    pub reclaimed: Money,
+}
+
+impl RoundSponsor {
+    /// Sponsor's pro rata share of what the round did not allocate, minus what they reclaimed
+    pub fn reclaimable(&self, round: &MatchingRound) -> Money {
+        // split in parts per billion first, deposit * unallocated could overflow u128
+        let share = self.deposit * MATCH_PRECISION / round.pool;
+        (round.unallocated / MATCH_PRECISION * share).saturating_sub(self.reclaimed)
+    }
+}
+
+/// Running tally of a crowdfund in a matching round
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct RoundEntry {
+    pub crowdfund_id: u64,
+    pub contributions: Money,
+    /// sum over backers of the square root of their total contribution in `QF_UNIT`s
+    pub sum_sqrt: u128,
+    /// share of the pool held for the crowdfund when the round was finalized
+    pub matched: Money,
+    /// whether `matched` was credited to the crowdfund or returned
+    pub settled: bool,
+}
+
+impl RoundEntry {
+    pub fn new(crowdfund_id: u64) -> Self {
+        RoundEntry {
+            crowdfund_id,
+            contributions: 0,
+            sum_sqrt: 0,
+            matched: 0,
+            settled: false,
+        }
+    }
+
+    /// Quadratic funding subsidy before the pool is split, (sum of roots)^2 - sum
+    pub fn quadratic_match(&self) -> u128 {
+        (self.sum_sqrt * self.sum_sqrt).saturating_sub(self.contributions / QF_UNIT)
+    }
+}
+
+/// Earlier version of the descriptive fields of a crowdfund
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct CrowdfundRevision {
+    pub version: u64,
+    pub title: String,
+    pub description: String,
+    pub donation_target: u128,
+    pub media: Option<String>,
+    /// when the next edit replaced this version
+    pub replaced_at: Timestamp,
 }
-#[derive(Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+
+/// Token a creator takes donations in, `target` is what alone would reach the donation target
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
+pub struct TokenTarget {
+    pub token_id: AccountId,
+    pub target: U128,
+}
+
+/// Escrow bookkeeping of one fungible token donated to a crowdfund
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct TokenEscrow {
+    pub token_id: AccountId,
+    /// amount of the token that alone reaches the donation target, set by the creator
+    pub target: Money,
+    pub total_donations: Money,
+    pub withdrawn: Money,
+    pub refunded: Money,
+}
+
+impl TokenEscrow {
+    pub fn new(token_id: AccountId) -> Self {
+        TokenEscrow {
+            token_id,
+            target: 0,
+            total_donations: 0,
+            withdrawn: 0,
+            refunded: 0,
+        }
+    }
+}
+
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
 pub struct Donation {
-    amount: Money,
-    donor: AccountId,
+    pub crowdfund_id: u64,
+    pub amount: Money,
+    pub donor: AccountId,
+    pub donated_at: Timestamp,
+    pub memo: Option<String>,
+    pub refunded: bool,
+    /// fungible token the amount is in, `None` for native NEAR
+    pub token_id: Option<AccountId>,
+    /// reward tier the donor selected
+    pub tier_id: Option<u64>,
 }
 impl Donation {
-    pub fn new() -> Self {
+    pub fn new(
+        crowdfund_id: u64,
+        donor: AccountId,
+        amount: Money,
+        memo: Option<String>,
+        tier_id: Option<u64>,
+    ) -> Self {
+        Donation {
+            crowdfund_id,
+            amount,
+            donor,
+            donated_at: env::block_timestamp(),
+            memo,
+            refunded: false,
+            token_id: None,
+            tier_id,
+        }
+    }
+
+    /// Donation carried over from the first contract layout, which neither linked donations
+    /// to a crowdfund nor held them in escrow, so nothing is owed on it
+    pub fn migrated(crowdfund_id: u64, donor: AccountId, amount: Money) -> Self {
+        Donation {
+            crowdfund_id,
+            amount,
+            donor,
+            donated_at: 0,
+            memo: None,
+            refunded: true,
+            token_id: None,
+            tier_id: None,
+        }
+    }
+
+    pub fn new_in_token(
+        crowdfund_id: u64,
+        donor: AccountId,
+        token_id: AccountId,
+        amount: Money,
+        memo: Option<String>,
+    ) -> Self {
         Donation {
-            amount: env::attached_deposit(),
+            crowdfund_id,
+            amount,
+            donor,
+            donated_at: env::block_timestamp(),
+            memo,
+            refunded: false,
+            token_id: Some(token_id),
+            tier_id: None,
+        }
+    }
+}
+
+/// Recurring donation. The donor deposits an allowance up front and `process_due_pledges`
+/// moves one instalment of it into the crowdfund's escrow every `interval`
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+pub struct Pledge {
+    pub id: u64,
+    pub crowdfund_id: u64,
+    pub donor: AccountId,
+    pub instalment: Money,
+    pub interval: Timestamp,
+    pub next_due_at: Timestamp,
+    /// part of the allowance not released yet, refunded when the pledge closes early
+    pub balance: Money,
+    pub released: Money,
+    pub active: bool,
+}
+
+impl Pledge {
+    pub fn new(
+        id: u64,
+        crowdfund_id: u64,
+        allowance: Money,
+        instalment: Money,
+        interval: Timestamp,
+    ) -> Self {
+        Pledge {
+            id,
+            crowdfund_id,
             donor: env::predecessor_account_id(),
+            instalment,
+            interval,
+            next_due_at: env::block_timestamp(),
+            balance: allowance,
+            released: 0,
+            active: true,
         }
     }
+
+    /// Takes the next instalment out of the balance, the last one is whatever is left.
+    /// After a late crank the pledge stays due until every overdue instalment was released
+    pub fn release_instalment(&mut self) -> Money {
+        let amount = std::cmp::min(self.instalment, self.balance);
+        self.balance -= amount;
+        self.released += amount;
+        self.next_due_at += self.interval;
+        self.active = self.balance > 0;
+        amount
+    }
 }
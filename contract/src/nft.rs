@@ -0,0 +1,326 @@
+use crate::events::{NftEvent, NftMintData, NftTransferData};
+use crate::*;
+use near_sdk::json_types::{Base64VecU8, ValidAccountId};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{serde_json, PromiseResult};
+
+/// Badges are numbered after the donation they were minted for
+pub type TokenId = String;
+
+pub const NFT_METADATA_SPEC: &str = "nft-1.0.0";
+pub const BADGE_NAME: &str = "Crowdfund Backer Badges";
+pub const BADGE_SYMBOL: &str = "BACKER";
+
+#[ext_contract(ext_nft_receiver)]
+pub trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> bool;
+}
+
+/// NEP-177 contract metadata
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NFTContractMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// NEP-177 token metadata
+#[derive(Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub copies: Option<u64>,
+    /// unix epoch in milliseconds
+    pub issued_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub starts_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// JSON naming the crowdfund and donation the badge was minted for
+    pub extra: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+/// NEP-171 token as returned by the views
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Token {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub metadata: Option<TokenMetadata>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Badge {
+    pub owner_id: AccountId,
+    pub metadata: TokenMetadata,
+}
+
+impl Badge {
+    fn to_token(&self, token_id: TokenId) -> Token {
+        Token {
+            token_id,
+            owner_id: self.owner_id.clone(),
+            metadata: Some(self.metadata.clone()),
+        }
+    }
+}
+
+/// Links a badge back to its crowdfund and donation, stored in `TokenMetadata::extra`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct BadgeExtra {
+    crowdfund_id: u64,
+    donation_id: u64,
+    amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_id: Option<AccountId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tier_id: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Approvals are not supported, so `approval_id` is ignored and only the owner transfers
+    #[payable]
+    pub fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        self.assert_not_paused();
+        assert_one_yocto();
+        let _ = approval_id;
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer_badge(&sender_id, receiver_id.as_ref(), &token_id, memo);
+    }
+
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> Promise {
+        self.assert_not_paused();
+        assert_one_yocto();
+        let _ = approval_id;
+        let sender_id = env::predecessor_account_id();
+        let receiver_id: AccountId = receiver_id.into();
+        self.internal_transfer_badge(&sender_id, &receiver_id, &token_id, memo);
+        ext_nft_receiver::nft_on_transfer(
+            sender_id.clone(),
+            sender_id.clone(),
+            token_id.clone(),
+            msg,
+            &receiver_id,
+            0,
+            NFT_ON_TRANSFER_GAS,
+        )
+        .then(ext_self::nft_resolve_transfer(
+            sender_id,
+            receiver_id,
+            token_id,
+            &env::current_account_id(),
+            0,
+            XCC_GAS,
+        ))
+    }
+
+    /// Callback for `nft_transfer_call`, hands the badge back when the receiver asks for it
+    /// or failed. Returns whether the transfer stands
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+    ) -> bool {
+        assert_self();
+        let must_revert = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            _ => true,
+        };
+        if !must_revert {
+            return true;
+        }
+        // the receiver may have passed the badge on already, then it stays with them
+        match self.badges.get(&token_id) {
+            Some(badge) if badge.owner_id == receiver_id => {
+                self.internal_transfer_badge(&receiver_id, &previous_owner_id, &token_id, None);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    pub fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        self.badges
+            .get(&token_id)
+            .map(|badge| badge.to_token(token_id))
+    }
+
+    pub fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: BADGE_NAME.to_string(),
+            symbol: BADGE_SYMBOL.to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    pub fn nft_total_supply(&self) -> U128 {
+        U128(self.badges.len() as u128)
+    }
+
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        self.badges
+            .iter()
+            .skip(from_index.map_or(0, |index| index.0 as usize))
+            .take(limit.unwrap_or(u64::MAX) as usize)
+            .map(|(token_id, badge)| badge.to_token(token_id))
+            .collect()
+    }
+
+    pub fn nft_supply_for_owner(&self, account_id: ValidAccountId) -> U128 {
+        U128(
+            self.badges_by_owner
+                .get(account_id.as_ref())
+                .map_or(0, |token_ids| token_ids.len() as u128),
+        )
+    }
+
+    pub fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        let token_ids = match self.badges_by_owner.get(account_id.as_ref()) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        token_ids
+            .iter()
+            .skip(from_index.map_or(0, |index| index.0 as usize))
+            .take(limit.unwrap_or(u64::MAX) as usize)
+            .map(|token_id| self.badges.get(&token_id).unwrap().to_token(token_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Mints the backer badge of a donation to its donor
+    pub(crate) fn internal_mint_badge(&mut self, crowdfund: &Crowdfund, donation_id: u64) {
+        let donation = self.donations.get(donation_id).unwrap();
+        let token_id: TokenId = donation_id.to_string();
+        let extra = BadgeExtra {
+            crowdfund_id: crowdfund.id,
+            donation_id,
+            amount: U128(donation.amount),
+            token_id: donation.token_id.clone(),
+            tier_id: donation.tier_id,
+        };
+        let metadata = TokenMetadata {
+            title: Some(format!("Backer of {}", crowdfund.title)),
+            description: Some(format!(
+                "Donated {} {} to crowdfund #{}",
+                donation.amount,
+                donation.token_id.as_deref().unwrap_or("yoctoNEAR"),
+                crowdfund.id
+            )),
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: Some((env::block_timestamp() / 1_000_000).to_string()),
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: Some(serde_json::to_string(&extra).unwrap()),
+            reference: None,
+            reference_hash: None,
+        };
+        self.badges.insert(
+            &token_id,
+            &Badge {
+                owner_id: donation.donor.clone(),
+                metadata,
+            },
+        );
+        self.internal_add_badge_to_owner(&donation.donor, &token_id);
+        NftEvent::NftMint(vec![NftMintData {
+            owner_id: donation.donor,
+            token_ids: vec![token_id],
+        }])
+        .emit();
+    }
+
+    fn internal_transfer_badge(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        memo: Option<String>,
+    ) {
+        let mut badge = self
+            .badges
+            .get(token_id)
+            .unwrap_or_else(|| ContractError::BadgeNotFound.panic());
+        require(&badge.owner_id == sender_id, ContractError::NotBadgeOwner);
+        require(
+            sender_id != receiver_id,
+            ContractError::InvalidBadgeReceiver,
+        );
+
+        let mut owner_badges = self.badges_by_owner.get(sender_id).unwrap();
+        owner_badges.remove(token_id);
+        if owner_badges.is_empty() {
+            self.badges_by_owner.remove(sender_id);
+        } else {
+            self.badges_by_owner.insert(sender_id, &owner_badges);
+        }
+        self.internal_add_badge_to_owner(receiver_id, token_id);
+        badge.owner_id = receiver_id.clone();
+        self.badges.insert(token_id, &badge);
+
+        NftEvent::NftTransfer(vec![NftTransferData {
+            old_owner_id: sender_id.clone(),
+            new_owner_id: receiver_id.clone(),
+            token_ids: vec![token_id.clone()],
+            memo,
+        }])
+        .emit();
+    }
+
+    fn internal_add_badge_to_owner(&mut self, owner_id: &AccountId, token_id: &TokenId) {
+        let mut owner_badges = self.badges_by_owner.get(owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::OwnerBadges {
+                account_hash: env::sha256(owner_id.as_bytes()),
+            })
+        });
+        owner_badges.insert(token_id);
+        self.badges_by_owner.insert(owner_id, &owner_badges);
+    }
+}
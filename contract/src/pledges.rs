@@ -0,0 +1,198 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Pledges the attached deposit to a crowdfund, released `instalment` by `instalment`
+    /// every `interval_days` by `process_due_pledges`. The first instalment is due at once
+    #[payable]
+    pub fn create_pledge(&mut self, id: u64, instalment: U128, interval_days: u64) -> u64 {
+        self.assert_not_paused();
+        let allowance = env::attached_deposit();
+        let instalment = instalment.0;
+        require(
+            instalment > 0 && instalment >= self.config.min_donation,
+            ContractError::DepositTooSmall,
+        );
+        require(
+            interval_days > 0 && allowance >= instalment,
+            ContractError::InvalidPledge,
+        );
+        let crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_accepting();
+        let initial_storage = env::storage_usage();
+        let pledge_id = self.pledges.len();
+        let pledge = Pledge::new(
+            pledge_id,
+            id,
+            allowance,
+            instalment,
+            interval_days * ONE_DAY,
+        );
+        self.pledges.push(&pledge);
+        self.pledge_schedule
+            .insert(&(pledge.next_due_at, pledge_id), &());
+        let mut donor_pledges = self.pledges_by_donor.get(&pledge.donor).unwrap_or_else(|| {
+            Vector::new(StorageKey::DonorPledges {
+                account_hash: env::sha256(pledge.donor.as_bytes()),
+            })
+        });
+        donor_pledges.push(&pledge_id);
+        self.pledges_by_donor.insert(&pledge.donor, &donor_pledges);
+        self.internal_charge_storage(&pledge.donor, initial_storage);
+        Event::PledgeCreated {
+            pledge_id,
+            crowdfund_id: id,
+            donor: pledge.donor,
+            allowance: U128(allowance),
+            instalment: U128(instalment),
+            interval: U64(pledge.interval),
+        }
+        .emit();
+        pledge_id
+    }
+
+    /// Stops a pledge and sends what was not released yet back to its donor
+    #[payable]
+    pub fn cancel_pledge(&mut self, pledge_id: u64) {
+        self.assert_not_paused();
+        assert_one_yocto();
+        let pledge = self.internal_get_pledge(pledge_id);
+        require(
+            pledge.donor == env::predecessor_account_id(),
+            ContractError::NotPledgeDonor,
+        );
+        require(pledge.active, ContractError::PledgeClosed);
+        self.internal_close_pledge(pledge);
+    }
+
+    /// Releases up to `limit` due instalments into their crowdfunds' escrow, earliest first,
+    /// anyone may call it. Pledges to crowdfunds that stopped taking donations, or whose donor
+    /// cannot pay for the storage of another donation, are closed and refunded instead.
+    /// Pledges to frozen crowdfunds are put off by one interval and not counted. Returns the
+    /// number of pledges handled
+    pub fn process_due_pledges(&mut self, limit: u64) -> u64 {
+        self.assert_not_paused();
+        let limit = std::cmp::min(limit, PLEDGE_BATCH_LIMIT);
+        let now = env::block_timestamp();
+        let mut processed: u64 = 0;
+        while processed < limit
+            && env::prepaid_gas().saturating_sub(env::used_gas()) >= PLEDGE_GAS_RESERVE
+        {
+            let key = match self.pledge_schedule.min() {
+                Some(key) if key.0 <= now => key,
+                _ => break,
+            };
+            // measured before the schedule entry goes, so putting it back is not charged twice
+            let initial_storage = env::storage_usage();
+            self.pledge_schedule.remove(&key);
+            let pledge_id = key.1;
+            let mut pledge = self.pledges.get(pledge_id).unwrap();
+            let crowdfund = self.internal_get_crowdfund(pledge.crowdfund_id);
+            if crowdfund.frozen {
+                // the owner may unfreeze it, look at the pledge again one interval later
+                pledge.next_due_at = now + pledge.interval;
+                self.pledges.replace(pledge_id, &pledge);
+                self.pledge_schedule
+                    .insert(&(pledge.next_due_at, pledge_id), &());
+                continue;
+            }
+            processed += 1;
+            if crowdfund.delisted
+                || crowdfund.status() != CrowdfundStatus::Active
+                || !self.internal_can_store_instalment(&pledge.donor)
+            {
+                // credits the schedule entry removed above
+                self.internal_charge_storage(&pledge.donor, initial_storage);
+                self.internal_close_pledge(pledge);
+                continue;
+            }
+            let amount = pledge.release_instalment();
+            if pledge.active {
+                self.pledge_schedule
+                    .insert(&(pledge.next_due_at, pledge_id), &());
+            }
+            self.pledges.replace(pledge_id, &pledge);
+            let donation_id = self.internal_donate(
+                crowdfund,
+                Donation::new(
+                    pledge.crowdfund_id,
+                    pledge.donor.clone(),
+                    amount,
+                    None,
+                    None,
+                ),
+            );
+            self.internal_charge_storage(&pledge.donor, initial_storage);
+            Event::PledgeInstalmentReleased {
+                pledge_id,
+                crowdfund_id: pledge.crowdfund_id,
+                donation_id,
+                amount: U128(amount),
+            }
+            .emit();
+        }
+        processed
+    }
+
+    pub fn get_pledge(&self, pledge_id: u64) -> PledgeView {
+        self.internal_get_pledge(pledge_id).into()
+    }
+
+    pub fn get_pledges_by_donor(
+        &self,
+        account_id: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PledgeView> {
+        let pledge_ids = match self.pledges_by_donor.get(&account_id) {
+            Some(pledge_ids) => pledge_ids,
+            None => return vec![],
+        };
+        let to_index = std::cmp::min(from_index.saturating_add(limit), pledge_ids.len());
+        (from_index..to_index)
+            .map(|index| {
+                self.pledges
+                    .get(pledge_ids.get(index).unwrap())
+                    .unwrap()
+                    .into()
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    fn internal_get_pledge(&self, pledge_id: u64) -> Pledge {
+        self.pledges
+            .get(pledge_id)
+            .unwrap_or_else(|| ContractError::PledgeNotFound.panic())
+    }
+
+    /// Whether the donor's storage deposit covers the state another instalment adds, so the
+    /// crank never fails on one underfunded account
+    fn internal_can_store_instalment(&self, donor: &AccountId) -> bool {
+        self.storage_accounts.get(donor).map_or(false, |account| {
+            account.available() >= PLEDGE_INSTALMENT_BYTES as Balance * env::storage_byte_cost()
+        })
+    }
+
+    fn internal_close_pledge(&mut self, mut pledge: Pledge) {
+        let initial_storage = env::storage_usage();
+        let refunded = pledge.balance;
+        pledge.balance = 0;
+        pledge.active = false;
+        self.pledges.replace(pledge.id, &pledge);
+        self.pledge_schedule
+            .remove(&(pledge.next_due_at, pledge.id));
+        self.internal_charge_storage(&pledge.donor, initial_storage);
+        if refunded > 0 {
+            Promise::new(pledge.donor.clone()).transfer(refunded);
+        }
+        Event::PledgeClosed {
+            pledge_id: pledge.id,
+            crowdfund_id: pledge.crowdfund_id,
+            donor: pledge.donor,
+            refunded: U128(refunded),
+        }
+        .emit();
+    }
+}
@@ -0,0 +1,72 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Offers a new reward tier on an active crowdfund, returns its id
+    pub fn add_reward_tier(&mut self, id: u64, tier: RewardTierInput) -> u64 {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_creator();
+        crowdfund.assert_accepting();
+        require(
+            crowdfund.reward_tiers.len() < MAX_REWARD_TIERS,
+            ContractError::TooManyRewardTiers,
+        );
+        let initial_storage = env::storage_usage();
+        let tier_id = crowdfund.reward_tiers.len() as u64;
+        let tier = RewardTier::new(tier_id, tier);
+        Event::RewardTierAdded {
+            crowdfund_id: id,
+            tier_id,
+            min_pledge: U128(tier.min_pledge),
+            quantity: tier.quantity,
+        }
+        .emit();
+        crowdfund.reward_tiers.push(tier);
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&crowdfund.creator, initial_storage);
+        tier_id
+    }
+
+    /// Changes the terms of a reward tier, e.g. to restock it or move its delivery date
+    pub fn update_reward_tier(&mut self, id: u64, tier_id: u64, tier: RewardTierInput) {
+        self.assert_not_paused();
+        let mut crowdfund = self.internal_get_crowdfund(id);
+        crowdfund.assert_creator();
+        crowdfund.assert_not_frozen();
+        let initial_storage = env::storage_usage();
+        let reward_tier = crowdfund.reward_tier_mut(tier_id);
+        reward_tier.update(tier);
+        Event::RewardTierUpdated {
+            crowdfund_id: id,
+            tier_id,
+            min_pledge: U128(reward_tier.min_pledge),
+            quantity: reward_tier.quantity,
+        }
+        .emit();
+        self.crowdfunds.replace(id, &crowdfund);
+        self.internal_charge_storage(&crowdfund.creator, initial_storage);
+    }
+
+    pub fn get_reward_tiers(&self, id: u64) -> Vec<RewardTierView> {
+        self.internal_get_crowdfund(id)
+            .reward_tiers
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Donations that selected a tier, in donation order, for the creator to fulfil
+    pub fn get_reward_tier_backers(
+        &self,
+        id: u64,
+        tier_id: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<DonationView> {
+        match self.donations_by_tier.get(&(id, tier_id)) {
+            Some(donation_ids) => self.internal_donations_page(&donation_ids, from_index, limit),
+            None => vec![],
+        }
+    }
+}
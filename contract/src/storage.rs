@@ -0,0 +1,189 @@
+use crate::*;
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::serde::Serialize;
+use near_sdk::StorageUsage;
+
+/// NEP-145 storage account, the `used` bytes are paid for out of the `total` deposit
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageAccount {
+    pub total: Balance,
+    pub used: StorageUsage,
+}
+
+impl StorageAccount {
+    pub fn new(total: Balance) -> Self {
+        StorageAccount {
+            total,
+            used: STORAGE_REGISTRATION_BYTES,
+        }
+    }
+
+    /// Part of the deposit not locked by the bytes the account uses
+    pub fn available(&self) -> Balance {
+        self.total
+            .saturating_sub(self.used as Balance * env::storage_byte_cost())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pays for the storage of `account_id`, the caller by default. With `registration_only`
+    /// only the minimum is kept and the rest of the deposit is refunded
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id: AccountId = account_id
+            .map(|account_id| account_id.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+        let min = storage_registration_cost();
+
+        let (mut account, deposit) = match self.storage_accounts.get(&account_id) {
+            Some(account) if registration_only => (account, 0),
+            Some(account) => (account, amount),
+            None => {
+                require(amount >= min, ContractError::StorageDepositTooSmall);
+                let deposit = if registration_only { min } else { amount };
+                (StorageAccount::new(0), deposit)
+            }
+        };
+        if deposit < amount {
+            Promise::new(env::predecessor_account_id()).transfer(amount - deposit);
+        }
+        account.total += deposit;
+        self.storage_accounts.insert(&account_id, &account);
+        if deposit > 0 {
+            Event::StorageDeposited {
+                account_id,
+                amount: U128(deposit),
+            }
+            .emit();
+        }
+        storage_balance(&account)
+    }
+
+    /// Sends `amount`, or everything not locked by used storage, back to the caller
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self.internal_get_storage_account(&account_id);
+        let available = account.available();
+        let amount = amount.map_or(available, |amount| amount.0);
+        require(amount <= available, ContractError::InsufficientStorage);
+        if amount > 0 {
+            account.total -= amount;
+            self.storage_accounts.insert(&account_id, &account);
+            Promise::new(account_id.clone()).transfer(amount);
+            Event::StorageWithdrawn {
+                account_id,
+                amount: U128(amount),
+            }
+            .emit();
+        }
+        storage_balance(&account)
+    }
+
+    /// Closes the caller's storage account and refunds its deposit. Accounts that still
+    /// hold crowdfunds, votes or donations cannot unregister, so `force` is not supported
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        require(!force.unwrap_or(false), ContractError::StorageInUse);
+        let account_id = env::predecessor_account_id();
+        let account = match self.storage_accounts.get(&account_id) {
+            Some(account) => account,
+            None => return false,
+        };
+        require(
+            account.used <= STORAGE_REGISTRATION_BYTES,
+            ContractError::StorageInUse,
+        );
+        self.storage_accounts.remove(&account_id);
+        if account.total > 0 {
+            Promise::new(account_id.clone()).transfer(account.total);
+        }
+        Event::StorageWithdrawn {
+            account_id,
+            amount: U128(account.total),
+        }
+        .emit();
+        true
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(storage_registration_cost()),
+            max: None,
+        }
+    }
+
+    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(account_id.as_ref())
+            .map(|account| storage_balance(&account))
+    }
+}
+
+fn storage_registration_cost() -> Balance {
+    STORAGE_REGISTRATION_BYTES as Balance * env::storage_byte_cost()
+}
+
+fn storage_balance(account: &StorageAccount) -> StorageBalance {
+    StorageBalance {
+        total: U128(account.total),
+        available: U128(account.available()),
+    }
+}
+
+impl Contract {
+    fn internal_get_storage_account(&self, account_id: &AccountId) -> StorageAccount {
+        self.storage_accounts
+            .get(account_id)
+            .unwrap_or_else(|| ContractError::StorageNotRegistered.panic())
+    }
+
+    /// Charges `account_id` for the bytes written since `initial_storage` was measured,
+    /// or gives back what was freed. Panics when the deposit does not cover the growth
+    pub(crate) fn internal_charge_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage: StorageUsage,
+    ) {
+        let mut account = self.internal_get_storage_account(account_id);
+        let current_storage = env::storage_usage();
+        if current_storage >= initial_storage {
+            account.used += current_storage - initial_storage;
+        } else {
+            account.used = std::cmp::max(
+                account
+                    .used
+                    .saturating_sub(initial_storage - current_storage),
+                STORAGE_REGISTRATION_BYTES,
+            );
+        }
+        require(
+            account.used as Balance * env::storage_byte_cost() <= account.total,
+            ContractError::InsufficientStorage,
+        );
+        self.storage_accounts.insert(account_id, &account);
+    }
+}
@@ -13,38 +13,153 @@ pub type Timestamp = u64;
 ///
 /// == CONSTANTS ================================================================
 ///
-/// TODO: revist MIN_ACCOUNT_BALANCE after some real data is included b/c this
-/// could end up being much higher
 /// ONE_NEAR = unit of NEAR token in yocto Ⓝ (1e24)
-pub const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000 as u128;
+pub const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
 /// XCC_GAS = gas for cross-contract calls, ~5 Tgas (teragas = 1e12) per "hop"
 pub const XCC_GAS: Gas = 20_000_000_000_000;
-/// MIN_ACCOUNT_BALANCE = 3 NEAR min to keep account alive via storage staking
-pub const MIN_ACCOUNT_BALANCE: u128 = ONE_NEAR * 3;
+/// FT_TRANSFER_GAS = gas for a NEP-141 ft_transfer on the token contract
+pub const FT_TRANSFER_GAS: Gas = 10_000_000_000_000;
+/// UPGRADE_GAS = gas kept by upgrade itself, the rest of the prepaid gas goes to migrate
+pub const UPGRADE_GAS: Gas = 10_000_000_000_000;
+/// NFT_ON_TRANSFER_GAS = gas for the receiver's nft_on_transfer in nft_transfer_call
+pub const NFT_ON_TRANSFER_GAS: Gas = 25_000_000_000_000;
+/// REFUND_BATCH_LIMIT = max transfers scheduled by one process_refunds call, fewer
+/// are scheduled when the attached gas runs out first
+pub const REFUND_BATCH_LIMIT: u64 = 10;
+/// REFUND_GAS_RESERVE = gas process_refunds keeps to save the cursor after the last transfer
+pub const REFUND_GAS_RESERVE: Gas = 10_000_000_000_000;
+/// ONE_DAY = one day in nanoseconds, the unit of block timestamps
+pub const ONE_DAY: Timestamp = 86_400_000_000_000;
+/// BASIS_POINTS = 100% expressed in basis points, the unit of the platform fee
+pub const BASIS_POINTS: u128 = 10_000;
+/// DEFAULT_MAX_DURATION_DAYS = longest campaign allowed until the owner changes it
+pub const DEFAULT_MAX_DURATION_DAYS: u64 = 365;
+/// MAX_DURATION_DAYS = upper bound the owner may set, keeps deadlines far from u64 overflow
+pub const MAX_DURATION_DAYS: u64 = 3_650;
+/// MAX_MILESTONES = most milestones a crowdfund may release its funds in
+pub const MAX_MILESTONES: usize = 10;
+/// MAX_REWARD_TIERS = most reward tiers a crowdfund may offer
+pub const MAX_REWARD_TIERS: usize = 20;
+/// MAX_ROUND_CROWDFUNDS = most crowdfunds one matching round can finalize within the gas limit
+pub const MAX_ROUND_CROWDFUNDS: usize = 50;
+/// QF_UNIT = milliNEAR, contributions are tallied in it so quadratic funding sums fit in u128
+pub const QF_UNIT: u128 = ONE_NEAR / 1_000;
+/// MATCH_PRECISION = parts per billion used to split a matching pool
+pub const MATCH_PRECISION: u128 = 1_000_000_000;
+/// MILESTONE_VOTING_PERIOD = how long backers have to vote on a milestone
+pub const MILESTONE_VOTING_PERIOD: Timestamp = ONE_DAY * 7;
+/// MILESTONE_QUORUM_BPS = share of the donations, tokens included, that must vote for a milestone
+/// to pass
+pub const MILESTONE_QUORUM_BPS: u128 = 2_000;
+/// STORAGE_REGISTRATION_BYTES = storage of a NEP-145 account record, the minimum deposit
+pub const STORAGE_REGISTRATION_BYTES: u64 = 150;
+/// PLEDGE_BATCH_LIMIT = most instalments one process_due_pledges call releases
+pub const PLEDGE_BATCH_LIMIT: u64 = 50;
+/// PLEDGE_INSTALMENT_BYTES = upper bound of the state one released instalment adds: the
+/// donation, its index entries and its backer badge
+pub const PLEDGE_INSTALMENT_BYTES: u64 = 2_000;
+/// PLEDGE_GAS_RESERVE = gas process_due_pledges keeps free before it handles another pledge
+pub const PLEDGE_GAS_RESERVE: Gas = 30_000_000_000_000;
+/// STATE_VERSION = layout of the persisted Contract struct, bumped with every migration
+pub const STATE_VERSION: u16 = 1;
+/// MAX_TITLE_LENGTH = longest crowdfund title, in bytes
+pub const MAX_TITLE_LENGTH: usize = 100;
+/// MAX_DESCRIPTION_LENGTH = longest crowdfund description, in bytes
+pub const MAX_DESCRIPTION_LENGTH: usize = 5_000;
+/// MAX_MEDIA_LENGTH = longest media URL or IPFS link, in bytes
+pub const MAX_MEDIA_LENGTH: usize = 512;
+/// MAX_CANCEL_REASON_LENGTH = longest reason given when cancelling a crowdfund, in bytes
+pub const MAX_CANCEL_REASON_LENGTH: usize = 500;
+/// IPFS_CID_V0_LENGTH = length of a base58 CIDv0, always starting with "Qm"
+const IPFS_CID_V0_LENGTH: usize = 46;
+/// IPFS_CID_V1_MIN_LENGTH = shortest base32 CIDv1 of a sha2-256 hash, starting with "b"
+const IPFS_CID_V1_MIN_LENGTH: usize = 59;
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
 /// == FUNCTIONS ================================================================
-/// Converts Yocto Ⓝ token quantity into NEAR, as a String
-pub fn asNEAR(amount: u128) -> String {
-    format!("{}", amount / ONE_NEAR)
-}
 /// Converts a quantity in NEAR into Yocto Ⓝ tokens
+#[allow(non_snake_case)]
 pub fn toYocto<D: Into<u128>>(amount: D) -> u128 {
     ONE_NEAR * amount.into()
 }
+/// Checks an http(s) URL: a host made of letters, digits, dots, dashes and an optional port,
+/// then any printable ASCII without whitespace
+pub fn is_valid_url(url: &str) -> bool {
+    let rest = match url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let host = rest.split(&['/', '?', '#'][..]).next().unwrap_or("");
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':')
+        && url.chars().all(|c| c.is_ascii_graphic())
+}
+/// Checks an IPFS content id, either a base58 CIDv0 or a base32 CIDv1
+pub fn is_valid_ipfs_cid(cid: &str) -> bool {
+    if cid.starts_with("Qm") {
+        cid.len() == IPFS_CID_V0_LENGTH && cid.chars().all(|c| BASE58_ALPHABET.contains(c))
+    } else if let Some(rest) = cid.strip_prefix('b') {
+        cid.len() >= IPFS_CID_V1_MIN_LENGTH
+            && rest
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+    } else {
+        false
+    }
+}
+/// Checks crowdfund media: an http(s) URL, a bare IPFS CID or `ipfs://<cid>[/path]`
+pub fn is_valid_media(media: &str) -> bool {
+    if media.len() > MAX_MEDIA_LENGTH {
+        return false;
+    }
+    match media.strip_prefix("ipfs://") {
+        Some(rest) => {
+            let mut parts = rest.splitn(2, '/');
+            is_valid_ipfs_cid(parts.next().unwrap_or(""))
+                && parts
+                    .next()
+                    .map_or(true, |path| path.chars().all(|c| c.is_ascii_graphic()))
+        }
+        None => is_valid_url(media) || is_valid_ipfs_cid(media),
+    }
+}
 /// Asserts that the contract has called itself
 pub fn assert_self() {
     let caller = env::predecessor_account_id();
     let current = env::current_account_id();
     assert_eq!(caller, current, "Only this contract may call itself");
 }
-/// Asserts that only a single promise was received, and successful
-pub fn assert_single_promise_success() {
+/// Largest integer whose square does not exceed `n`
+pub fn integer_sqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+/// Asserts that exactly one yoctoNEAR is attached, which requires a full access key
+pub fn assert_one_yocto() {
+    assert_eq!(
+        env::attached_deposit(),
+        1,
+        "Requires attached deposit of exactly 1 yoctoNEAR"
+    );
+}
+/// Returns whether the single promise received was successful
+pub fn is_promise_success() -> bool {
     assert_eq!(
         env::promise_results_count(),
         1,
         "Expected exactly one promise result",
     );
-    match env::promise_result(0) {
-        PromiseResult::Successful(_) => return,
-        _ => panic!("Expected PromiseStatus to be successful"),
-    };
+    matches!(env::promise_result(0), PromiseResult::Successful(_))
 }
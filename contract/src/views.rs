@@ -0,0 +1,310 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+// JSON shapes of what the contract returns. Storage structs stay Borsh only, amounts go out
+// as `U128` and timestamps as `U64` so JavaScript clients do not lose digits above 2^53
+
+/// Platform parameters as read by `get_config` and written by `set_config`
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PlatformConfigView {
+    /// smallest accepted donation, in yocto
+    pub min_donation: U128,
+    pub max_duration_days: u64,
+    /// platform fee charged on NEAR withdrawals, in basis points
+    pub fee_bps: u16,
+}
+
+impl From<PlatformConfig> for PlatformConfigView {
+    fn from(config: PlatformConfig) -> Self {
+        PlatformConfigView {
+            min_donation: U128(config.min_donation),
+            max_duration_days: config.max_duration_days,
+            fee_bps: config.fee_bps,
+        }
+    }
+}
+
+impl From<PlatformConfigView> for PlatformConfig {
+    fn from(config: PlatformConfigView) -> Self {
+        PlatformConfig {
+            min_donation: config.min_donation.0,
+            max_duration_days: config.max_duration_days,
+            fee_bps: config.fee_bps,
+        }
+    }
+}
+
+/// Lightweight view of a crowdfund for listings, without description and voters
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrowdfundSummary {
+    pub id: u64,
+    pub creator: AccountId,
+    pub created_at: U64,
+    pub deadline: U64,
+    pub title: String,
+    pub media: Option<String>,
+    pub donation_target: U128,
+    pub total_donations: U128,
+    pub total_votes: u64,
+    pub status: CrowdfundStatus,
+}
+
+/// Everything about one crowdfund except its donations and voters, which are paged separately
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrowdfundView {
+    pub id: u64,
+    pub creator: AccountId,
+    pub created_at: U64,
+    pub deadline: U64,
+    pub title: String,
+    pub description: String,
+    pub media: Option<String>,
+    pub donation_target: U128,
+    pub total_donations: U128,
+    pub withdrawn: U128,
+    pub refunded: U128,
+    /// NEAR credited from matching pools, already part of `total_donations`
+    pub matched: U128,
+    pub total_votes: u64,
+    pub status: CrowdfundStatus,
+    pub cancel_reason: Option<String>,
+    pub accepted_tokens: Vec<AccountId>,
+    pub milestones: Vec<MilestoneView>,
+    pub reward_tiers: Vec<RewardTierView>,
+    pub matching_round: Option<u64>,
+    pub version: u64,
+}
+
+/// Totals across every crowdfund, delisted ones included
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractStats {
+    pub crowdfunds: u64,
+    pub active_crowdfunds: u64,
+    pub successful_crowdfunds: u64,
+    pub donations: u64,
+    /// NEAR donated and matched, before fees and refunds
+    pub total_raised: U128,
+    pub total_withdrawn: U128,
+    pub total_refunded: U128,
+    pub treasury: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrowdfundPayout {
+    pub gross_raised: U128,
+    pub fees: U128,
+    pub net_payout: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DonationView {
+    pub crowdfund_id: u64,
+    pub amount: U128,
+    pub donor: AccountId,
+    pub donated_at: U64,
+    pub memo: Option<String>,
+    pub refunded: bool,
+    /// fungible token the amount is in, `None` for native NEAR
+    pub token_id: Option<AccountId>,
+    /// reward tier the donor selected
+    pub tier_id: Option<u64>,
+}
+
+impl From<Donation> for DonationView {
+    fn from(donation: Donation) -> Self {
+        DonationView {
+            crowdfund_id: donation.crowdfund_id,
+            amount: U128(donation.amount),
+            donor: donation.donor,
+            donated_at: U64(donation.donated_at),
+            memo: donation.memo,
+            refunded: donation.refunded,
+            token_id: donation.token_id,
+            tier_id: donation.tier_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MilestoneView {
+    pub title: String,
+    /// part of the NEAR raised released by this milestone, in basis points
+    pub share_bps: u16,
+    pub due_date: U64,
+    pub status: MilestoneStatus,
+    pub voting_ends_at: U64,
+    pub approval_weight: U128,
+    pub rejection_weight: U128,
+}
+
+impl From<Milestone> for MilestoneView {
+    fn from(milestone: Milestone) -> Self {
+        MilestoneView {
+            title: milestone.title,
+            share_bps: milestone.share_bps,
+            due_date: U64(milestone.due_date),
+            status: milestone.status,
+            voting_ends_at: U64(milestone.voting_ends_at),
+            approval_weight: U128(milestone.approval_weight),
+            rejection_weight: U128(milestone.rejection_weight),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardTierView {
+    pub id: u64,
+    pub min_pledge: U128,
+    pub description: String,
+    pub quantity: Option<u64>,
+    pub claimed: u64,
+    pub estimated_delivery: U64,
+}
+
+impl From<RewardTier> for RewardTierView {
+    fn from(tier: RewardTier) -> Self {
+        RewardTierView {
+            id: tier.id,
+            min_pledge: U128(tier.min_pledge),
+            description: tier.description,
+            quantity: tier.quantity,
+            claimed: tier.claimed,
+            estimated_delivery: U64(tier.estimated_delivery),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MatchingRoundView {
+    pub id: u64,
+    pub pool: U128,
+    pub ends_at: U64,
+    /// most of the pool a single crowdfund may receive, in basis points
+    pub match_cap_bps: u16,
+    pub entries: Vec<RoundEntryView>,
+    pub finalized: bool,
+    pub unallocated: U128,
+}
+
+impl From<MatchingRound> for MatchingRoundView {
+    fn from(round: MatchingRound) -> Self {
+        MatchingRoundView {
+            id: round.id,
+            pool: U128(round.pool),
+            ends_at: U64(round.ends_at),
+            match_cap_bps: round.match_cap_bps,
+            entries: round.entries.into_iter().map(Into::into).collect(),
+            finalized: round.finalized,
+            unallocated: U128(round.unallocated),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoundEntryView {
+    pub crowdfund_id: u64,
+    pub contributions: U128,
+    /// sum over backers of the square root of their total contribution in milliNEAR
+    pub sum_sqrt: U128,
+    pub matched: U128,
+    pub settled: bool,
+}
+
+impl From<RoundEntry> for RoundEntryView {
+    fn from(entry: RoundEntry) -> Self {
+        RoundEntryView {
+            crowdfund_id: entry.crowdfund_id,
+            contributions: U128(entry.contributions),
+            sum_sqrt: U128(entry.sum_sqrt),
+            matched: U128(entry.matched),
+            settled: entry.settled,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CrowdfundRevisionView {
+    pub version: u64,
+    pub title: String,
+    pub description: String,
+    pub donation_target: U128,
+    pub media: Option<String>,
+    /// when the next edit replaced this version
+    pub replaced_at: U64,
+}
+
+impl From<CrowdfundRevision> for CrowdfundRevisionView {
+    fn from(revision: CrowdfundRevision) -> Self {
+        CrowdfundRevisionView {
+            version: revision.version,
+            title: revision.title,
+            description: revision.description,
+            donation_target: U128(revision.donation_target),
+            media: revision.media,
+            replaced_at: U64(revision.replaced_at),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PledgeView {
+    pub id: u64,
+    pub crowdfund_id: u64,
+    pub donor: AccountId,
+    pub instalment: U128,
+    pub interval: U64,
+    pub next_due_at: U64,
+    pub balance: U128,
+    pub released: U128,
+    pub active: bool,
+}
+
+impl From<Pledge> for PledgeView {
+    fn from(pledge: Pledge) -> Self {
+        PledgeView {
+            id: pledge.id,
+            crowdfund_id: pledge.crowdfund_id,
+            donor: pledge.donor,
+            instalment: U128(pledge.instalment),
+            interval: U64(pledge.interval),
+            next_due_at: U64(pledge.next_due_at),
+            balance: U128(pledge.balance),
+            released: U128(pledge.released),
+            active: pledge.active,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenEscrowView {
+    pub token_id: AccountId,
+    pub target: U128,
+    pub total_donations: U128,
+    pub withdrawn: U128,
+    pub refunded: U128,
+}
+
+impl From<TokenEscrow> for TokenEscrowView {
+    fn from(escrow: TokenEscrow) -> Self {
+        TokenEscrowView {
+            token_id: escrow.token_id,
+            target: U128(escrow.target),
+            total_donations: U128(escrow.total_donations),
+            withdrawn: U128(escrow.withdrawn),
+            refunded: U128(escrow.refunded),
+        }
+    }
+}
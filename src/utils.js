@@ -11,8 +11,8 @@ export async function initContract() {
   window.accountId = window.walletConnection.getAccountId()
 
   window.contract = await new Contract(window.walletConnection.account(), nearConfig.contractName, {
-    viewMethods: ['list_crowdfunds', 'crowdfund_count', 'get_total_donations'],
-    changeMethods: ['add_crowdfund', 'add_vote', 'add_donation'],
+    viewMethods: ['list_crowdfunds', 'list_crowdfunds_by_creator', 'list_crowdfunds_by_status', 'list_crowdfunds_by_progress', 'list_crowdfunds_sorted', 'crowdfund_count', 'get_total_donations', 'get_state_version', 'get_crowdfund', 'get_crowdfund_summary', 'get_progress', 'get_contract_stats', 'get_pledge', 'get_pledges_by_donor', 'get_crowdfund_status', 'get_donations_for_crowdfund', 'get_donations_by_donor', 'has_voted', 'get_owner', 'get_config', 'is_paused', 'get_accepted_tokens', 'get_crowdfund_tokens', 'get_crowdfund_payout', 'get_treasury', 'get_milestones', 'get_reward_tiers', 'get_reward_tier_backers', 'nft_token', 'nft_metadata', 'nft_total_supply', 'nft_tokens', 'nft_supply_for_owner', 'nft_tokens_for_owner', 'get_matching_round', 'list_matching_rounds', 'storage_balance_bounds', 'storage_balance_of', 'get_crowdfund_revisions'],
+    changeMethods: ['add_crowdfund', 'add_vote', 'remove_vote', 'add_donation', 'withdraw', 'claim_refund', 'process_refunds', 'propose_owner', 'accept_ownership', 'pause', 'unpause', 'set_crowdfund_delisted', 'set_crowdfund_frozen', 'set_config', 'add_accepted_token', 'remove_accepted_token', 'set_crowdfund_tokens', 'withdraw_token', 'withdraw_treasury', 'start_milestone_vote', 'vote_milestone', 'finalize_milestone', 'add_reward_tier', 'update_reward_tier', 'nft_transfer', 'nft_transfer_call', 'create_matching_round', 'fund_matching_round', 'join_matching_round', 'finalize_matching_round', 'settle_match', 'reclaim_matching_funds', 'storage_deposit', 'storage_withdraw', 'storage_unregister', 'edit_crowdfund', 'cancel_crowdfund', 'upgrade', 'create_pledge', 'cancel_pledge', 'process_due_pledges'],
   })
 }
 